@@ -1,71 +1,66 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
 use itertools::iproduct;
-use man_ray::camera::Camera;
 use man_ray::collision::Collidable;
+use man_ray::film::Film;
 use man_ray::material::{Dialectic, Lambertian, Material, Metal};
+use man_ray::ray::Ray;
+use man_ray::scene::Scene;
+use man_ray::settings::RenderSettings;
 use man_ray::shape::Sphere;
 use man_ray::vector::Vector;
 use man_ray::world::World;
 use rand::prelude::*;
 use rand::rngs::SmallRng;
 use rand::thread_rng;
+use std::sync::Arc;
 
-fn big_scene(world: &World, scale: usize, mut rng: &mut SmallRng) {
-    let origin = Vector::new(8.0, 2.0, 3.0);
-    let target = Vector::new(0.0, 1.0, 0.0);
-    let vup = Vector::new(0.0, 1.0, 0.0);
-    let field_of_view = 33.0;
-    let aspect_ratio = 4.0 / 3.0;
-    let apurture = 0.0;
-    let camera = Camera::new(origin, target, vup, field_of_view, aspect_ratio, apurture);
-    camera.render(&world, scale, &mut rng);
-}
-
-fn setup(n: usize, rng: &mut SmallRng) -> World {
-    let ns = (n as f64).sqrt() as isize;
-    let mut objects: Vec<Box<dyn Collidable>> = Vec::with_capacity(n);
+fn scene_objects(n: usize, rng: &mut SmallRng) -> Vec<Box<dyn Collidable>> {
+    // About `n` small spheres on a square grid, plus the four large ones.
+    let side = (n as f64).sqrt().round() as isize;
+    let (start, end) = (-side / 2, side - side / 2);
+    let mut objects: Vec<Box<dyn Collidable>> = Vec::with_capacity(n + 4);
 
     let ground = Box::new(Sphere::new(
         Vector::new(0.0, -1000.0, 0.0),
         1000.0,
-        Box::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
     ));
     let dialectic = Box::new(Sphere::new(
         Vector::new(0.0, 1.0, 0.0),
         1.0,
-        Box::new(Dialectic::new(1.5)),
+        Arc::new(Dialectic::new(1.5)),
     ));
     let lambertian = Box::new(Sphere::new(
         Vector::new(-4.0, 1.0, 0.0),
         1.0,
-        Box::new(Lambertian::new(Vector::new(0.4, 0.2, 0.1))),
+        Arc::new(Lambertian::new(Vector::new(0.4, 0.2, 0.1))),
     ));
     let metal = Box::new(Sphere::new(
         Vector::new(4.0, 1.0, 0.0),
         1.0,
-        Box::new(Metal::new(Vector::new(0.7, 0.6, 0.5), 0.0)),
+        Arc::new(Metal::new(Vector::new(0.7, 0.6, 0.5), 0.0)),
     ));
     objects.push(ground);
     objects.push(dialectic);
     objects.push(lambertian);
     objects.push(metal);
 
-    for (i, e) in iproduct!(-ns..ns, -ns..ns) {
+    for (i, e) in iproduct!(start..end, start..end) {
         let choose_mat = rng.gen::<f64>();
         let center = Vector::new(
             i as f64 + 0.9 + rng.gen::<f64>(),
             0.2,
             e as f64 + 0.9 * rng.gen::<f64>(),
         );
-        let material: Box<dyn Material> = if (center - Vector::new(4.0, 0.2, 0.0)).length() > 0.9 {
-            Box::new(Lambertian::new(Vector::new(
+        let material: Arc<dyn Material> = if (center - Vector::new(4.0, 0.2, 0.0)).length() > 0.9 {
+            Arc::new(Lambertian::new(Vector::new(
                 rng.gen::<f64>() * rng.gen::<f64>(),
                 rng.gen::<f64>() * rng.gen::<f64>(),
                 rng.gen::<f64>() * rng.gen::<f64>(),
             )))
         } else if choose_mat < 0.95 {
-            Box::new(Metal::new(
+            Arc::new(Metal::new(
                 Vector::new(
                     0.5 * (1.0 + rng.gen::<f64>()),
                     0.5 * (1.0 + rng.gen::<f64>()),
@@ -74,21 +69,79 @@ fn setup(n: usize, rng: &mut SmallRng) -> World {
                 0.5 * rng.gen::<f64>(),
             ))
         } else {
-            Box::new(Dialectic::new(1.5))
+            Arc::new(Dialectic::new(1.5))
         };
         objects.push(Box::new(Sphere::new(center, 0.2, material)));
     }
-    World::new(objects)
+    objects
+}
+
+fn setup(n: usize, rng: &mut SmallRng) -> World {
+    World::new(scene_objects(n, rng))
+}
+
+fn random_rays(count: usize, rng: &mut SmallRng) -> Vec<Ray> {
+    let origin = Vector::new(8.0, 2.0, 3.0);
+    (0..count)
+        .map(|_| {
+            let target = Vector::new(
+                rng.gen::<f64>() * 200.0 - 100.0,
+                rng.gen::<f64>() * 2.0 - 1.0,
+                rng.gen::<f64>() * 200.0 - 100.0,
+            );
+            Ray::new(origin, target - origin, 0.0)
+        })
+        .collect()
 }
 
 fn criterion_benchmark(c: &mut Criterion) {
     let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
-    let world = setup(1, &mut rng);
-    let scale = 50;
+    let scene = Scene::parse(include_str!("../scenes/example.toml")).unwrap();
+    let width = 50;
+    let height = (width as f64 / scene.camera.aspect()).round() as usize;
+    let settings = RenderSettings {
+        environment: Arc::clone(&scene.environment),
+        ..RenderSettings::default()
+    };
 
     c.bench_function("big scene", move |b| {
-        b.iter(|| big_scene(&world, scale, &mut rng))
+        b.iter(|| {
+            let mut film = Film::new(width, height);
+            scene
+                .camera
+                .render(&scene.world, &mut film, &settings, rng.gen());
+            film
+        })
     });
+
+    c.bench_function_over_inputs(
+        "world build",
+        |b, &&n| {
+            b.iter_with_setup(
+                || {
+                    let mut rng = SmallRng::seed_from_u64(n as u64);
+                    scene_objects(n, &mut rng)
+                },
+                World::new,
+            )
+        },
+        &[100, 1_000, 10_000],
+    );
+
+    c.bench_function_over_inputs(
+        "world collision",
+        |b, &&n| {
+            let mut rng = SmallRng::seed_from_u64(n as u64);
+            let world = setup(n, &mut rng);
+            let rays = random_rays(1_000, &mut rng);
+            b.iter(|| {
+                rays.iter()
+                    .filter(|ray| world.check_collision(**ray, 0.001, f64::MAX).is_some())
+                    .count()
+            })
+        },
+        &[100, 1_000, 10_000, 100_000],
+    );
 }
 
 criterion_group!(benches, criterion_benchmark);
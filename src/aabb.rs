@@ -0,0 +1,121 @@
+use std::f64;
+
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vector,
+    pub max: Vector,
+}
+
+impl Aabb {
+    pub fn new(min: Vector, max: Vector) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    // Bounds for shapes that extend forever, such as planes.
+    pub fn infinite() -> Aabb {
+        Aabb {
+            min: Vector::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            max: Vector::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        (0..3).all(|axis| self.min[axis].is_finite() && self.max[axis].is_finite())
+    }
+
+    pub fn from_points(points: &[Vector]) -> Aabb {
+        points
+            .iter()
+            .fold(Aabb::empty(), |bounds, point| bounds.grow(*point))
+    }
+
+    pub fn union(&self, other: Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(&self, point: Vector) -> Aabb {
+        Aabb {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    pub fn centroid(&self) -> Vector {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vector {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let extent = self.extent();
+        if extent.x < 0.0 || extent.y < 0.0 || extent.z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
+    pub fn largest_axis(&self) -> usize {
+        let extent = self.extent();
+        if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    // The part of the ray between `tmin` and `tmax` that lies inside the box, if any.
+    pub fn clip(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<(f64, f64)> {
+        let mut start = tmin;
+        let mut end = tmax;
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            let (near, far) = if inverse < 0.0 { (t1, t0) } else { (t0, t1) };
+            start = start.max(near);
+            end = end.min(far);
+            if end <= start {
+                return None;
+            }
+        }
+        Some((start, end))
+    }
+
+    // Slab test; `inverse_direction` is passed in so traversal computes it once per ray.
+    pub fn hit(&self, ray: Ray, inverse_direction: Vector, tmin: f64, tmax: f64) -> bool {
+        let mut tmin = tmin;
+        let mut tmax = tmax;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse_direction[axis];
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse_direction[axis];
+            let (near, far) = if inverse_direction[axis] < 0.0 {
+                (t1, t0)
+            } else {
+                (t0, t1)
+            };
+            tmin = tmin.max(near);
+            tmax = tmax.min(far);
+            if tmax < tmin {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,197 @@
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::f64;
+use std::path::Path;
+
+use crate::camera::random_in_unit_disc;
+
+// The shape of the lens opening, which out of focus highlights take on. Points are drawn
+// between -1 and 1 on both axes and scaled by the camera's lens radius.
+pub trait Aperture: Send + Sync {
+    fn sample(&self, rng: &mut SmallRng) -> (f64, f64);
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Circle;
+
+impl Aperture for Circle {
+    fn sample(&self, rng: &mut SmallRng) -> (f64, f64) {
+        let point = random_in_unit_disc(rng);
+        (point.x, point.y)
+    }
+}
+
+// A regular polygon inscribed in the unit circle, like the opening left by a diaphragm with
+// `sides` straight blades. `rotation` turns it counterclockwise, in degrees.
+#[derive(Clone, Copy, Debug)]
+pub struct Polygon {
+    sides: usize,
+    rotation: f64,
+}
+
+impl Polygon {
+    pub fn new(sides: usize, rotation: f64) -> Polygon {
+        assert!(
+            sides >= 3,
+            "a polygon needs at least 3 sides, got {}",
+            sides
+        );
+        Polygon {
+            sides,
+            rotation: rotation * f64::consts::PI / 180.0,
+        }
+    }
+
+    fn corner(&self, index: usize) -> (f64, f64) {
+        let angle = self.rotation + index as f64 * 2.0 * f64::consts::PI / self.sides as f64;
+        (angle.cos(), angle.sin())
+    }
+}
+
+impl Aperture for Polygon {
+    // Every wedge between the center and one side has the same area, so one is picked
+    // uniformly and a point drawn uniformly within it.
+    fn sample(&self, rng: &mut SmallRng) -> (f64, f64) {
+        let side = rng.gen_range(0, self.sides);
+        let (ax, ay) = self.corner(side);
+        let (bx, by) = self.corner(side + 1);
+
+        let (mut s, mut t): (f64, f64) = (rng.gen(), rng.gen());
+        if s + t > 1.0 {
+            s = 1.0 - s;
+            t = 1.0 - t;
+        }
+        (ax * s + bx * t, ay * s + by * t)
+    }
+}
+
+// An aperture drawn as an image: brighter pixels let more light through. The image is centered
+// on the lens with its longer side spanning the lens's diameter.
+pub struct ImageAperture {
+    width: usize,
+    height: usize,
+    // Running total of pixel weights, row by row from the top.
+    cdf: Vec<f64>,
+}
+
+impl ImageAperture {
+    pub fn new(width: usize, height: usize, weights: Vec<f64>) -> ImageAperture {
+        assert_eq!(
+            weights.len(),
+            width * height,
+            "aperture image has {} pixels for {}x{}",
+            weights.len(),
+            width,
+            height
+        );
+        let mut total = 0.0;
+        let cdf = weights
+            .into_iter()
+            .map(|weight| {
+                total += weight.max(0.0);
+                total
+            })
+            .collect();
+        ImageAperture { width, height, cdf }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<ImageAperture> {
+        let image = image::open(path)?.to_luma();
+        let (width, height) = image.dimensions();
+        let weights = image
+            .pixels()
+            .map(|pixel| f64::from(pixel.data[0]) / 255.0)
+            .collect();
+        Ok(ImageAperture::new(width as usize, height as usize, weights))
+    }
+
+    // Whether any of the image lets light through.
+    pub fn is_open(&self) -> bool {
+        self.cdf.last().is_some_and(|&total| total > 0.0)
+    }
+}
+
+impl Aperture for ImageAperture {
+    fn sample(&self, rng: &mut SmallRng) -> (f64, f64) {
+        let total = match self.cdf.last() {
+            Some(&total) if total > 0.0 => total,
+            _ => return (0.0, 0.0),
+        };
+        let target = rng.gen::<f64>() * total;
+        let index = self
+            .cdf
+            .partition_point(|&running| running <= target)
+            .min(self.cdf.len() - 1);
+        let (x, y) = (index % self.width, index / self.width);
+
+        let size = self.width.max(self.height) as f64;
+        let u = (x as f64 + rng.gen::<f64>() - self.width as f64 / 2.0) / size * 2.0;
+        let v = (self.height as f64 / 2.0 - y as f64 - rng.gen::<f64>()) / size * 2.0;
+        (u, v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 10_000;
+
+    #[test]
+    fn circle_samples_stay_inside() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..SAMPLES {
+            let (x, y) = Circle.sample(&mut rng);
+            assert!(x * x + y * y <= 1.0);
+        }
+    }
+
+    #[test]
+    fn polygon_samples_stay_inside() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        for &(sides, rotation) in &[(3, 0.0), (5, 20.0), (8, -45.0)] {
+            let polygon = Polygon::new(sides, rotation);
+            let mut furthest: f64 = 0.0;
+            for _ in 0..SAMPLES {
+                let (x, y) = polygon.sample(&mut rng);
+                // Corners run counterclockwise, so the inside is left of every side.
+                for side in 0..sides {
+                    let (ax, ay) = polygon.corner(side);
+                    let (bx, by) = polygon.corner(side + 1);
+                    let cross = (bx - ax) * (y - ay) - (by - ay) * (x - ax);
+                    assert!(cross >= -1e-12, "({}, {}) outside {} sides", x, y, sides);
+                }
+                furthest = furthest.max((x * x + y * y).sqrt());
+            }
+            // Samples reach out towards the corners too.
+            assert!(furthest > 0.9, "{}", furthest);
+        }
+    }
+
+    #[test]
+    fn image_samples_stay_in_open_pixels() {
+        // A 4x2 image open only at (2, 0), which covers x from 0 to 0.5 and y from 0 to 0.5
+        // once the 4 pixel width spans the lens's diameter.
+        let mut weights = vec![0.0; 8];
+        weights[2] = 1.0;
+        let aperture = ImageAperture::new(4, 2, weights);
+        assert!(aperture.is_open());
+        let mut rng = SmallRng::seed_from_u64(3);
+        for _ in 0..SAMPLES {
+            let (x, y) = aperture.sample(&mut rng);
+            assert!(
+                (0.0..=0.5).contains(&x) && (0.0..=0.5).contains(&y),
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn closed_images_sample_the_center() {
+        let aperture = ImageAperture::new(2, 2, vec![0.0; 4]);
+        assert!(!aperture.is_open());
+        assert_eq!(aperture.sample(&mut SmallRng::seed_from_u64(4)), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,460 @@
+use rand::rngs::SmallRng;
+
+use crate::aabb::Aabb;
+use crate::collision::{Collidable, Collision};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+const BIN_COUNT: usize = 16;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Aabb,
+    // For leaves, the first object in `Bvh::objects`; for interior nodes, the index of the
+    // second child (the first child always immediately follows its parent).
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+impl Node {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct BuildEntry {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bin {
+    bounds: Aabb,
+    count: usize,
+}
+
+impl Bin {
+    fn empty() -> Bin {
+        Bin {
+            bounds: Aabb::empty(),
+            count: 0,
+        }
+    }
+}
+
+pub struct Bvh<T: Collidable> {
+    objects: Vec<T>,
+    nodes: Vec<Node>,
+}
+
+impl<T: Collidable> Bvh<T> {
+    pub fn new(objects: Vec<T>) -> Bvh<T> {
+        let mut entries: Vec<BuildEntry> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bounds = object.bounding_box();
+                BuildEntry {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(2 * entries.len());
+        if !entries.is_empty() {
+            build(&mut entries, 0, &mut nodes);
+        }
+
+        let mut slots: Vec<Option<T>> = objects.into_iter().map(Some).collect();
+        let objects = entries
+            .iter()
+            .map(|entry| slots[entry.index].take().unwrap())
+            .collect();
+
+        Bvh { objects, nodes }
+    }
+
+    pub fn objects(&self) -> &[T] {
+        &self.objects
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+fn build(entries: &mut [BuildEntry], offset: usize, nodes: &mut Vec<Node>) -> usize {
+    let node_index = nodes.len();
+    let bounds = entries
+        .iter()
+        .fold(Aabb::empty(), |acc, entry| acc.union(entry.bounds));
+    nodes.push(Node {
+        bounds,
+        offset,
+        count: entries.len(),
+        axis: 0,
+    });
+
+    if entries.len() == 1 {
+        return node_index;
+    }
+
+    let centroid_bounds = entries
+        .iter()
+        .fold(Aabb::empty(), |acc, entry| acc.grow(entry.centroid));
+
+    let split = match find_split(entries, bounds, centroid_bounds) {
+        Some(split) => split,
+        None => return node_index,
+    };
+
+    let (axis, bin) = split;
+    let mid = partition(entries, |entry| {
+        bin_index(entry.centroid, centroid_bounds, axis) <= bin
+    });
+
+    build(&mut entries[..mid], offset, nodes);
+    let right = build(&mut entries[mid..], offset + mid, nodes);
+
+    nodes[node_index] = Node {
+        bounds,
+        offset: right,
+        count: 0,
+        axis,
+    };
+    node_index
+}
+
+// Binned surface-area heuristic. Returns the axis and the last bin of the left partition,
+// or `None` if the entries are cheaper to intersect as a single leaf.
+fn find_split(
+    entries: &[BuildEntry],
+    bounds: Aabb,
+    centroid_bounds: Aabb,
+) -> Option<(usize, usize)> {
+    let leaf_cost = INTERSECTION_COST * entries.len() as f64;
+    let area = bounds.surface_area();
+    let extent = centroid_bounds.extent();
+
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        if extent[axis] <= 0.0 {
+            continue;
+        }
+
+        let mut bins = [Bin::empty(); BIN_COUNT];
+        for entry in entries.iter() {
+            let bin = &mut bins[bin_index(entry.centroid, centroid_bounds, axis)];
+            bin.bounds = bin.bounds.union(entry.bounds);
+            bin.count += 1;
+        }
+
+        let mut right_areas = [0.0; BIN_COUNT];
+        let mut right_bounds = Aabb::empty();
+        for bin in (1..BIN_COUNT).rev() {
+            right_bounds = right_bounds.union(bins[bin].bounds);
+            right_areas[bin] = right_bounds.surface_area();
+        }
+
+        let mut left_bounds = Aabb::empty();
+        let mut left_count = 0;
+        for bin in 0..BIN_COUNT - 1 {
+            left_bounds = left_bounds.union(bins[bin].bounds);
+            left_count += bins[bin].count;
+            let right_count = entries.len() - left_count;
+            if left_count == 0 || right_count == 0 {
+                continue;
+            }
+
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (left_bounds.surface_area() * left_count as f64
+                        + right_areas[bin + 1] * right_count as f64)
+                    / area;
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, bin));
+            }
+        }
+    }
+
+    match best {
+        Some((cost, axis, bin)) if cost < leaf_cost || entries.len() > MAX_LEAF_SIZE => {
+            Some((axis, bin))
+        }
+        _ => None,
+    }
+}
+
+fn bin_index(centroid: Vector, centroid_bounds: Aabb, axis: usize) -> usize {
+    let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+    let relative = (centroid[axis] - centroid_bounds.min[axis]) / extent;
+    ((relative * BIN_COUNT as f64) as usize).min(BIN_COUNT - 1)
+}
+
+fn partition<F: Fn(&BuildEntry) -> bool>(entries: &mut [BuildEntry], predicate: F) -> usize {
+    let mut mid = 0;
+    for i in 0..entries.len() {
+        if predicate(&entries[i]) {
+            entries.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+impl<T: Collidable> Bvh<T> {
+    // The closest collision `hit` finds among the objects, given each object and the
+    // distance to beat.
+    pub(crate) fn closest<'a, F>(
+        &'a self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        mut hit: F,
+    ) -> Option<Collision<'a>>
+    where
+        F: FnMut(&'a T, f64) -> Option<Collision<'a>>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse_direction = Vector::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+
+        let mut closest_collision: Option<Collision> = None;
+        let mut closest_time = tmax;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.hit(ray, inverse_direction, tmin, closest_time) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                for object in &self.objects[node.offset..node.offset + node.count] {
+                    if let Some(collision) = hit(object, closest_time) {
+                        closest_time = collision.time;
+                        closest_collision = Some(collision);
+                    }
+                }
+            } else if ray.direction[node.axis] < 0.0 {
+                stack.push(node_index + 1);
+                stack.push(node.offset);
+            } else {
+                stack.push(node.offset);
+                stack.push(node_index + 1);
+            }
+        }
+        closest_collision
+    }
+}
+
+impl<T: Collidable> Collidable for Bvh<T> {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        self.closest(ray, tmin, tmax, |object, limit| {
+            object.check_collision(ray, tmin, limit)
+        })
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        self.closest(ray, tmin, tmax, |object, limit| {
+            object.sample_collision(ray, tmin, limit, rng)
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or_else(Aabb::empty, |node| node.bounds)
+    }
+
+    // Every object along the ray contributes, so unlike `check_collision` this cannot narrow
+    // the search as it goes; it stops as soon as the ray is blocked instead.
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, rng: &mut SmallRng) -> f64 {
+        if self.nodes.is_empty() {
+            return 1.0;
+        }
+
+        let inverse_direction = Vector::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+
+        let mut transmittance = 1.0;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if !node.bounds.hit(ray, inverse_direction, tmin, tmax) {
+                continue;
+            }
+
+            if node.is_leaf() {
+                for object in &self.objects[node.offset..node.offset + node.count] {
+                    transmittance *= object.transmittance(ray, tmin, tmax, rng);
+                    if transmittance == 0.0 {
+                        return 0.0;
+                    }
+                }
+            } else {
+                stack.push(node.offset);
+                stack.push(node_index + 1);
+            }
+        }
+        transmittance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::random_unit_vector;
+    use crate::material::{Lambertian, Material};
+    use crate::shape::{Sphere, Triangle};
+    use rand::prelude::*;
+    use std::f64;
+    use std::sync::Arc;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)))
+    }
+
+    fn random_point(rng: &mut SmallRng, extent: f64) -> Vector {
+        Vector::new(
+            rng.gen_range(-extent, extent),
+            rng.gen_range(-extent, extent),
+            rng.gen_range(-extent, extent),
+        )
+    }
+
+    fn linear_scan<'a>(
+        objects: &'a [Box<dyn Collidable>],
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+    ) -> Option<Collision<'a>> {
+        objects
+            .iter()
+            .filter_map(|object| object.check_collision(ray, tmin, tmax))
+            .min_by(|a, b| a.time.partial_cmp(&b.time).unwrap())
+    }
+
+    // Checks the tree against testing every object, from origins both among the objects and
+    // well outside them. Returns how many of the rays hit something.
+    fn assert_matches_linear_scan(objects: Vec<Box<dyn Collidable>>, rng: &mut SmallRng) -> usize {
+        let bvh = Bvh::new(objects);
+        let objects = bvh.objects();
+        let mut hits = 0;
+        for _ in 0..2000 {
+            let origin = random_point(rng, 15.0);
+            // Half the rays aim near an object, the rest go anywhere and mostly miss.
+            let direction = if rng.gen() {
+                let target = objects[rng.gen_range(0, objects.len())].bounding_box();
+                target.centroid() + random_point(rng, 0.5) - origin
+            } else {
+                random_unit_vector(rng)
+            };
+            let ray = Ray::new(origin, direction, 0.0);
+            let found = bvh.check_collision(ray, 0.001, f64::MAX);
+            let expected = linear_scan(objects, ray, 0.001, f64::MAX);
+            match (&found, &expected) {
+                (Some(found), Some(expected)) => {
+                    assert_eq!(found.time, expected.time);
+                    hits += 1;
+                }
+                (None, None) => {}
+                _ => panic!(
+                    "tree found {:?}, linear scan {:?}",
+                    found.as_ref().map(|collision| collision.time),
+                    expected.as_ref().map(|collision| collision.time)
+                ),
+            }
+        }
+        hits
+    }
+
+    #[test]
+    fn nearest_hit_matches_linear_scan() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let objects = (0..200)
+            .map(|_| {
+                let radius = rng.gen_range(0.1, 1.0);
+                Box::new(Sphere::new(
+                    random_point(&mut rng, 10.0),
+                    radius,
+                    material(),
+                )) as Box<dyn Collidable>
+            })
+            .collect();
+        let hits = assert_matches_linear_scan(objects, &mut rng);
+        assert!(hits > 500 && hits < 1500, "{} hits", hits);
+    }
+
+    #[test]
+    fn handles_degenerate_bounds() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let mut objects: Vec<Box<dyn Collidable>> = Vec::new();
+        // Flat triangles, whose boxes have no depth along one axis.
+        for axis in 0..3 {
+            for _ in 0..20 {
+                let center = random_point(&mut rng, 10.0);
+                let mut vertex = || {
+                    let offset = random_point(&mut rng, 1.0);
+                    center
+                        + match axis {
+                            0 => Vector::new(0.0, offset.y, offset.z),
+                            1 => Vector::new(offset.x, 0.0, offset.z),
+                            _ => Vector::new(offset.x, offset.y, 0.0),
+                        }
+                };
+                let vertices = [center, vertex(), vertex()];
+                objects.push(Box::new(Triangle::new(vertices, material())));
+            }
+        }
+        // Spheres sharing a center, so their centroids cannot be split apart.
+        let shared = random_point(&mut rng, 5.0);
+        for index in 0..20 {
+            let radius = 0.1 + index as f64 * 0.05;
+            objects.push(Box::new(Sphere::new(shared, radius, material())));
+        }
+        // A sphere of no size at all.
+        objects.push(Box::new(Sphere::new(Vector::default(), 0.0, material())));
+
+        let hits = assert_matches_linear_scan(objects, &mut rng);
+        assert!(hits > 200 && hits < 1500, "{} hits", hits);
+    }
+
+    #[test]
+    fn empty_tree_hits_nothing() {
+        let bvh: Bvh<Sphere> = Bvh::new(Vec::new());
+        let ray = Ray::new(Vector::default(), Vector::new(0.0, 0.0, 1.0), 0.0);
+        assert!(bvh.check_collision(ray, 0.0, f64::MAX).is_none());
+        assert!(bvh.is_empty());
+    }
+}
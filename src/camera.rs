@@ -1,22 +1,36 @@
 use itertools::iproduct;
 use rand::prelude::*;
 use rand::rngs::SmallRng;
+use rayon::prelude::*;
 use std::f64;
+use std::sync::Arc;
 
+use crate::aperture::{Aperture, Circle};
+use crate::collision::Collision;
 use crate::color::Color;
+use crate::film::Film;
+use crate::projection::{Perspective, Projection};
 use crate::ray::Ray;
+use crate::settings::RenderSettings;
+use crate::stereo::Stereo;
 use crate::vector::Vector;
 use crate::world::World;
 
 pub struct Camera {
     origin: Vector,
-    lower_left_corner: Vector,
-    horizontal: Vector,
-    vertical: Vector,
+    // The camera's right, up and backwards directions.
     u: Vector,
     v: Vector,
+    w: Vector,
+    projection: Arc<dyn Projection>,
     aspect: f64,
     lens_radius: f64,
+    aperture: Arc<dyn Aperture>,
+    focus_distance: f64,
+    autofocus: Option<(f64, f64)>,
+    shutter_open: f64,
+    shutter_close: f64,
+    stereo: Option<Stereo>,
 }
 
 impl Camera {
@@ -28,114 +42,639 @@ impl Camera {
         aspect: f64,
         apurture: f64,
     ) -> Camera {
-        let focus_dist = (origin - target).length();
+        Camera::projected(
+            origin,
+            target,
+            vup,
+            Arc::new(Perspective::new(fov)),
+            aspect,
+            apurture,
+        )
+    }
+
+    // A camera at `origin` looking towards `target` through any projection.
+    pub fn projected(
+        origin: Vector,
+        target: Vector,
+        vup: Vector,
+        projection: Arc<dyn Projection>,
+        aspect: f64,
+        apurture: f64,
+    ) -> Camera {
         let lens_radius = apurture / 2.0;
-        let theta = fov * f64::consts::PI / 180.0;
-        let half_height = (theta / 2.0).tan();
-        let half_width = aspect * half_height;
 
         let w = (origin - target).to_unit();
         let u = vup.cross(w).to_unit();
         let v = w.cross(u);
 
-        let horizontal_part = u * half_width * focus_dist;
-        let vertical_part = v * half_height * focus_dist;
-        let depth_part = w * focus_dist;
-
-        let lower_left_corner = origin - horizontal_part - vertical_part - depth_part;
-        let horizontal = horizontal_part * 2.0;
-        let vertical = vertical_part * 2.0;
         Camera {
             origin,
-            lower_left_corner,
-            horizontal,
-            vertical,
             u,
             v,
+            w,
+            projection,
             aspect,
             lens_radius,
+            aperture: Arc::new(Circle),
+            focus_distance: (origin - target).length(),
+            autofocus: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            stereo: None,
         }
     }
 
-    fn get_ray(self: &Self, s: f64, t: f64, rng: &mut SmallRng) -> Ray {
-        let random_disc = random_in_unit_disc(rng) * self.lens_radius;
-        let offset = self.u * random_disc.x + self.v * random_disc.y;
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + (self.horizontal * s) + (self.vertical * t)
-                - self.origin
-                - offset,
-        )
+    // Keeps things `distance` in front of the camera sharp, instead of at the target.
+    pub fn with_focus_distance(self, distance: f64) -> Camera {
+        Camera {
+            focus_distance: distance,
+            ..self
+        }
     }
 
-    pub fn render(self: &Self, world: &World, scale: usize, rng: &mut SmallRng) -> Vec<Color> {
-        let width = scale;
-        let height = (scale as f64 / self.aspect) as usize;
+    // Focuses on whatever is seen first at (`x`, `y`), as fractions of the frame's width and
+    // height from its top left corner. When nothing is there the focus distance is kept.
+    pub fn with_autofocus(self, x: f64, y: f64) -> Camera {
+        Camera {
+            autofocus: Some((x, y)),
+            ..self
+        }
+    }
 
-        let sub_pixels = 100;
+    pub fn with_aperture(self, aperture: Arc<dyn Aperture>) -> Camera {
+        Camera { aperture, ..self }
+    }
 
-        let mut pixels: Vec<Color> = Vec::with_capacity(width * height);
-        for (i, e) in iproduct!(0..height, 0..width) {
-            let color: Color = (0..sub_pixels)
-                .map(|_| {
-                    let u_rand: f64 = rng.gen();
-                    let u = (e as f64 + u_rand) / width as f64;
+    // Rays are cast at random times between `open` and `close`, so anything that moves in
+    // that interval is blurred. The shutter is closed instantly at time zero by default.
+    pub fn with_shutter(self, open: f64, close: f64) -> Camera {
+        Camera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
 
-                    let v_rand: f64 = rng.gen();
-                    let v = (((height as f64) - (i as f64)) + v_rand) / (height as f64);
-                    let ray = self.get_ray(u, v, rng);
+    // Renders a view for each eye, both into the same frame.
+    pub fn with_stereo(self, stereo: Stereo) -> Camera {
+        Camera {
+            stereo: Some(stereo),
+            ..self
+        }
+    }
 
-                    calc_color(world, ray, 0, rng)
-                })
-                .sum();
-            pixels.push((color / sub_pixels).gamma_correct());
+    // The size of the frame holding a `width` by `height` view for every eye.
+    pub fn frame_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match self.stereo {
+            Some(stereo) => stereo.layout.frame_size(width, height),
+            None => (width, height),
         }
-        pixels
     }
-}
 
-fn calc_color(world: &World, ray: Ray, depth: i32, rng: &mut SmallRng) -> Color {
-    if let Some(collision) = world.check_collision(ray, 0.001, f64::MAX) {
-        if depth < 50 {
-            match collision.material.scatter(ray, collision, rng) {
-                Some(effect) => {
-                    calc_color(world, effect.scatter, depth + 1, rng) * effect.attenuation
-                }
-                None => Color::default(),
+    // The aspect ratio the scene asks for, to size the film by. Renders always take theirs
+    // from the film itself.
+    pub fn aspect(&self) -> f64 {
+        self.aspect
+    }
+
+    // The ray through (`s`, `t`) on a view `aspect` times wider than it is tall, from its
+    // bottom left corner, before the lens and shutter are applied. `eye` moves it that far
+    // towards the right eye of a stereo pair, or the left when negative.
+    fn view_ray(&self, s: f64, t: f64, eye: f64, aspect: f64) -> Option<Ray> {
+        let local = self.projection.ray(s * 2.0 - 1.0, t * 2.0 - 1.0, aspect)?;
+        let (origin, direction) = match self.stereo {
+            Some(stereo) => {
+                let offset = self.projection.eye_offset(local.direction) * eye;
+                (
+                    local.origin + offset,
+                    local.direction - offset / stereo.convergence,
+                )
             }
+            None => (local.origin, local.direction),
+        };
+        let to_world = |local: Vector| self.u * local.x + self.v * local.y + self.w * local.z;
+        Some(Ray::new(
+            self.origin + to_world(origin),
+            to_world(direction),
+            self.shutter_open,
+        ))
+    }
+
+    fn get_ray(
+        &self,
+        s: f64,
+        t: f64,
+        view: View,
+        focus_distance: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Ray> {
+        let view = self.view_ray(s, t, view.eye, view.aspect())?;
+        let (x, y) = self.aperture.sample(rng);
+        let offset = self.u * (x * self.lens_radius) + self.v * (y * self.lens_radius);
+        let time = if self.shutter_close > self.shutter_open {
+            self.shutter_open + rng.gen::<f64>() * (self.shutter_close - self.shutter_open)
         } else {
-            Color::default()
+            self.shutter_open
+        };
+        Some(Ray::new(
+            view.origin + offset,
+            view.direction * focus_distance - offset,
+            time,
+        ))
+    }
+
+    // The focus distance to render with, in lengths of the projection's view directions, so
+    // an autofocus hit's ray parameter is already that distance.
+    fn resolve_focus(&self, world: &World, settings: &RenderSettings, aspect: f64) -> f64 {
+        let ray = match self
+            .autofocus
+            .and_then(|(x, y)| self.view_ray(x, 1.0 - y, 0.0, aspect))
+        {
+            Some(ray) => ray,
+            None => return self.focus_distance,
+        };
+        match world.check_collision(ray, settings.epsilon, f64::MAX) {
+            Some(collision) => collision.time,
+            None => self.focus_distance,
+        }
+    }
+
+    // The views making up a `width` by `height` frame: one, or one for each eye.
+    fn views(&self, width: usize, height: usize) -> Vec<View> {
+        let stereo = match self.stereo {
+            Some(stereo) => stereo,
+            None => {
+                return vec![View {
+                    eye: 0.0,
+                    x: 0,
+                    y: 0,
+                    width,
+                    height,
+                }]
+            }
+        };
+        let (view_width, view_height) = stereo.layout.view_size(width, height);
+        let (right_x, right_y) = stereo.layout.right_offset(width, height);
+        let half = stereo.interocular / 2.0;
+        vec![
+            View {
+                eye: -half,
+                x: 0,
+                y: 0,
+                width: view_width,
+                height: view_height,
+            },
+            View {
+                eye: half,
+                x: right_x,
+                y: right_y,
+                width: view_width,
+                height: view_height,
+            },
+        ]
+    }
+
+    pub fn render(&self, world: &World, film: &mut Film, settings: &RenderSettings, seed: u64) {
+        assert!(
+            settings.samples > 0,
+            "render needs at least one sample per pixel"
+        );
+        let views = self.views(film.width(), film.height());
+        let focus_distance = self.resolve_focus(world, settings, views[0].aspect());
+        // Both eyes share seeds, so their noise matches and does not shimmer between them.
+        let rendered_tiles: Vec<(View, Tile, Vec<Color>)> = views
+            .into_par_iter()
+            .flat_map(|view| {
+                Tile::split(view.width, view.height)
+                    .into_par_iter()
+                    .map(move |tile| (view, tile))
+            })
+            .map(|(view, tile)| {
+                let mut rng = SmallRng::seed_from_u64(tile.seed(seed));
+                let pixels =
+                    self.render_tile(world, tile, view, focus_distance, settings, &mut rng);
+                (view, tile, pixels)
+            })
+            .collect();
+
+        for (view, tile, tile_pixels) in rendered_tiles {
+            for ((i, e), color) in tile.pixels().zip(tile_pixels) {
+                film.set(view.x + e, view.y + i, color);
+            }
+        }
+    }
+
+    fn render_tile(
+        &self,
+        world: &World,
+        tile: Tile,
+        view: View,
+        focus_distance: f64,
+        settings: &RenderSettings,
+        rng: &mut SmallRng,
+    ) -> Vec<Color> {
+        let (width, height) = (view.width, view.height);
+        tile.pixels()
+            .map(|(i, e)| {
+                let color: Color = (0..settings.samples)
+                    .map(|_| {
+                        let u_rand: f64 = rng.gen();
+                        let u = (e as f64 + u_rand) / width as f64;
+
+                        let v_rand: f64 = rng.gen();
+                        let v = (((height as f64) - (i as f64)) + v_rand) / (height as f64);
+                        match self.get_ray(u, v, view, focus_distance, rng) {
+                            Some(ray) => calc_color(world, ray, settings, rng),
+                            None => Color::default(),
+                        }
+                    })
+                    .sum();
+                color / settings.samples
+            })
+            .collect()
+    }
+}
+
+// One eye's part of the frame: how far towards the right eye it looks from, and the region
+// of the frame it fills.
+#[derive(Clone, Copy, Debug)]
+struct View {
+    eye: f64,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl View {
+    fn aspect(&self) -> f64 {
+        self.width as f64 / self.height as f64
+    }
+}
+
+const TILE_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug)]
+struct Tile {
+    index: usize,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Tile {
+    fn split(width: usize, height: usize) -> Vec<Tile> {
+        iproduct!(
+            (0..height).step_by(TILE_SIZE),
+            (0..width).step_by(TILE_SIZE)
+        )
+        .enumerate()
+        .map(|(index, (y, x))| Tile {
+            index,
+            x,
+            y,
+            width: TILE_SIZE.min(width - x),
+            height: TILE_SIZE.min(height - y),
+        })
+        .collect()
+    }
+
+    fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        iproduct!(self.y..self.y + self.height, self.x..self.x + self.width)
+    }
+
+    // Each tile gets its own generator so the output does not depend on which thread
+    // (or in what order) the tile is rendered.
+    fn seed(&self, seed: u64) -> u64 {
+        seed ^ (self.index as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+fn calc_color(world: &World, ray: Ray, settings: &RenderSettings, rng: &mut SmallRng) -> Color {
+    let mut radiance = Color::default();
+    let mut throughput = Color::new(1.0, 1.0, 1.0);
+    let mut ray = ray;
+    // The density the current ray was scattered with; `None` for camera rays and specular
+    // bounces, whose emitted light is never also picked up by light sampling.
+    let mut scatter_pdf: Option<f64> = None;
+
+    for depth in 0..=settings.max_depth {
+        let collision = match world.sample_collision(ray, settings.epsilon, f64::MAX, rng) {
+            Some(collision) => collision,
+            None => {
+                let direction = ray.direction.to_unit();
+                let weight = match scatter_pdf {
+                    Some(pdf) => power_heuristic(pdf, settings.environment.pdf(direction)),
+                    None => 1.0,
+                };
+                radiance =
+                    radiance + throughput * settings.environment.radiance(direction) * weight;
+                break;
+            }
+        };
+
+        let emitted = collision.material.emitted(&collision);
+        if !emitted.is_black() {
+            let weight = match scatter_pdf {
+                Some(pdf) => power_heuristic(pdf, world.light_pdf(ray.origin, &collision)),
+                None => 1.0,
+            };
+            radiance = radiance + throughput * emitted * weight;
         }
-    } else {
-        background(ray)
+
+        if depth == settings.max_depth {
+            break;
+        }
+
+        radiance = radiance + throughput * direct_light(world, ray, &collision, settings, rng);
+        radiance = radiance + throughput * environment_light(world, ray, &collision, settings, rng);
+        radiance = radiance + throughput * directional_light(world, ray, &collision, settings, rng);
+
+        match collision.material.scatter(ray, collision, rng) {
+            Some(effect) => {
+                throughput = throughput * effect.attenuation;
+                scatter_pdf = effect.pdf;
+                ray = effect.scatter;
+            }
+            None => break,
+        }
+    }
+    radiance
+}
+
+// Next-event estimation: light arriving at `collision` straight from a sampled point on one of
+// the world's lights, weighted against the chance of the BSDF scattering towards it.
+fn direct_light(
+    world: &World,
+    ray: Ray,
+    collision: &Collision,
+    settings: &RenderSettings,
+    rng: &mut SmallRng,
+) -> Color {
+    let sample = match world.sample_light(collision.point, rng) {
+        Some(sample) if sample.pdf > 0.0 => sample,
+        _ => return Color::default(),
+    };
+
+    let offset = sample.point - collision.point;
+    let distance = offset.length();
+    let direction = offset / distance;
+    let bsdf = collision.material.evaluate(ray, collision, direction);
+    if bsdf.is_black() {
+        return Color::default();
+    }
+
+    let shadow_ray = Ray::new(collision.point, direction, ray.time);
+    let transmittance = world.transmittance(shadow_ray, settings.epsilon, distance * 0.9999, rng);
+    if transmittance == 0.0 {
+        return Color::default();
     }
+    let emitted = match world.check_collision(shadow_ray, distance * 0.9999, distance * 1.0001) {
+        Some(hit) => hit.material.emitted(&hit),
+        None => return Color::default(),
+    };
+
+    let scatter_pdf = collision.material.pdf(ray, collision, direction);
+    emitted * bsdf * (transmittance * power_heuristic(sample.pdf, scatter_pdf) / sample.pdf)
+}
+
+// Next-event estimation towards a direction sampled from the environment, for environments
+// that support it.
+fn environment_light(
+    world: &World,
+    ray: Ray,
+    collision: &Collision,
+    settings: &RenderSettings,
+    rng: &mut SmallRng,
+) -> Color {
+    let sample = match settings.environment.sample(rng) {
+        Some(sample) if sample.pdf > 0.0 => sample,
+        _ => return Color::default(),
+    };
+
+    let bsdf = collision
+        .material
+        .evaluate(ray, collision, sample.direction);
+    if bsdf.is_black() {
+        return Color::default();
+    }
+
+    let shadow_ray = Ray::new(collision.point, sample.direction, ray.time);
+    let transmittance = world.transmittance(shadow_ray, settings.epsilon, f64::MAX, rng);
+    if transmittance == 0.0 {
+        return Color::default();
+    }
+
+    let scatter_pdf = collision.material.pdf(ray, collision, sample.direction);
+    sample.radiance * bsdf * (transmittance * power_heuristic(sample.pdf, scatter_pdf) / sample.pdf)
+}
+
+// Light from the world's directional lights. They cannot be hit by scattered rays, so each is
+// always sampled directly and needs no weighting.
+fn directional_light(
+    world: &World,
+    ray: Ray,
+    collision: &Collision,
+    settings: &RenderSettings,
+    rng: &mut SmallRng,
+) -> Color {
+    world
+        .directional_lights()
+        .iter()
+        .map(|light| {
+            let direction = light.sample(rng);
+            let bsdf = collision.material.evaluate(ray, collision, direction);
+            if bsdf.is_black() {
+                return Color::default();
+            }
+            let shadow_ray = Ray::new(collision.point, direction, ray.time);
+            light.irradiance()
+                * bsdf
+                * world.transmittance(shadow_ray, settings.epsilon, f64::MAX, rng)
+        })
+        .sum()
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let pdf_squared = pdf * pdf;
+    let total = pdf_squared + other_pdf * other_pdf;
+    if total == 0.0 {
+        return 0.0;
+    }
+    pdf_squared / total
 }
 
 pub fn random_in_unit_sphere(rng: &mut SmallRng) -> Vector {
     let mut point;
     loop {
-        point = Vector::new(rng.gen(), rng.gen(), rng.gen()) * 1.5 - Vector::unit();
-        if point.dot(point) >= 1.0 {
+        point = Vector::new(rng.gen(), rng.gen(), rng.gen()) * 2.0 - Vector::unit();
+        if point.dot(point) < 1.0 {
             break;
         }
     }
     point
 }
 
+pub fn random_unit_vector(rng: &mut SmallRng) -> Vector {
+    let z: f64 = rng.gen::<f64>() * 2.0 - 1.0;
+    let phi = rng.gen::<f64>() * 2.0 * f64::consts::PI;
+    let radius = (1.0 - z * z).sqrt();
+    Vector::new(radius * phi.cos(), radius * phi.sin(), z)
+}
+
 pub fn random_in_unit_disc(rng: &mut SmallRng) -> Vector {
     let mut point;
     loop {
         point = Vector::new(rng.gen(), rng.gen(), 0.0) * 2.0 - Vector::new(1.0, 1.0, 0.0);
-        if point.dot(point) >= 1.0 {
+        if point.dot(point) < 1.0 {
             break;
         }
     }
     point
 }
 
-fn background(ray: Ray) -> Color {
-    let unit_direction = ray.direction.to_unit();
-    let t = 0.5 * unit_direction.y + 1.0;
-    let vector = Vector::unit() * (1.0 - t) + Vector::new(0.5, 0.7, 1.0) * t;
-    Color::from_vector(vector)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aperture::Polygon;
+    use crate::material::Lambertian;
+    use crate::projection::Equirectangular;
+    use crate::scene::Scene;
+    use crate::shape::Sphere;
+    use crate::stereo::{Stereo, StereoLayout};
+
+    fn render_with_threads(threads: usize) -> Vec<(f64, f64, f64)> {
+        let scene =
+            Scene::load(concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/lights.toml")).unwrap();
+        let settings = RenderSettings {
+            samples: 2,
+            max_depth: 4,
+            environment: Arc::clone(&scene.environment),
+            ..RenderSettings::default()
+        };
+        // Not a multiple of the tile size either way, so edge tiles are partial.
+        let mut film = Film::new(37, 21);
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| scene.camera.render(&scene.world, &mut film, &settings, 7));
+        film.pixels()
+            .iter()
+            .map(|pixel| (pixel.red, pixel.green, pixel.blue))
+            .collect()
+    }
+
+    #[test]
+    fn renders_the_same_on_any_number_of_threads() {
+        let single = render_with_threads(1);
+        assert!(single.iter().any(|&pixel| pixel != (0.0, 0.0, 0.0)));
+        assert_eq!(single, render_with_threads(4));
+        assert_eq!(single, render_with_threads(3));
+    }
+
+    // Looking down -z at a sphere whose near side is 4 units away, with another behind it.
+    fn focus_world() -> World {
+        let material = Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5)));
+        World::new(vec![
+            Box::new(Sphere::new(
+                Vector::new(0.0, 0.0, -5.0),
+                1.0,
+                material.clone(),
+            )),
+            Box::new(Sphere::new(Vector::new(0.0, 0.0, -10.0), 3.0, material)),
+        ])
+    }
+
+    fn focus_camera() -> Camera {
+        Camera::new(
+            Vector::default(),
+            Vector::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.5,
+        )
+        .with_focus_distance(7.0)
+    }
+
+    #[test]
+    fn autofocus_picks_the_first_hit() {
+        let world = focus_world();
+        let settings = RenderSettings::default();
+        let camera = focus_camera().with_autofocus(0.5, 0.5);
+        assert!((camera.resolve_focus(&world, &settings, 1.5) - 4.0).abs() < 1e-9);
+
+        // Nothing is seen in the corner, so the set distance is kept.
+        let missed = focus_camera().with_autofocus(0.0, 0.0);
+        assert_eq!(missed.resolve_focus(&world, &settings, 1.5), 7.0);
+        assert_eq!(focus_camera().resolve_focus(&world, &settings, 1.5), 7.0);
+    }
+
+    #[test]
+    fn lens_samples_stay_inside_the_aperture() {
+        let camera = focus_camera().with_aperture(Arc::new(Polygon::new(6, 0.0)));
+        let view = camera.views(30, 20)[0];
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let (s, t) = (rng.gen(), rng.gen());
+            let ray = camera.get_ray(s, t, view, 7.0, &mut rng).unwrap();
+            // The lens lies across the view, within the lens radius of the camera.
+            assert!(ray.origin.z.abs() < 1e-12);
+            assert!(ray.origin.length() <= camera.lens_radius + 1e-12);
+        }
+    }
+
+    #[test]
+    fn stereo_eyes_take_their_halves() {
+        let camera = focus_camera().with_stereo(Stereo::new(0.1, 2.0));
+        let views = camera.views(200, 50);
+        assert_eq!(views.len(), 2);
+        let (left, right) = (views[0], views[1]);
+        assert!(left.eye < 0.0 && right.eye > 0.0);
+        assert_eq!((left.x, left.y, left.width, left.height), (0, 0, 100, 50));
+        assert_eq!(
+            (right.x, right.y, right.width, right.height),
+            (100, 0, 100, 50)
+        );
+
+        let camera = camera.with_stereo(Stereo::new(0.1, 2.0).with_layout(StereoLayout::TopBottom));
+        let views = camera.views(100, 100);
+        assert_eq!((views[0].x, views[0].y, views[0].height), (0, 0, 50));
+        assert_eq!((views[1].x, views[1].y, views[1].height), (0, 50, 50));
+    }
+
+    #[test]
+    fn stereo_eyes_sit_apart_and_converge() {
+        let camera = focus_camera().with_stereo(Stereo::new(0.1, 2.0));
+        let left = camera.view_ray(0.5, 0.5, -0.05, 1.0).unwrap();
+        let right = camera.view_ray(0.5, 0.5, 0.05, 1.0).unwrap();
+        // The camera's right is +x here.
+        assert!((left.origin.x + 0.05).abs() < 1e-12);
+        assert!((right.origin.x - 0.05).abs() < 1e-12);
+        // Both look at the same point `convergence` away.
+        let meet = |ray: Ray| ray.origin + ray.direction * (2.0 / -ray.direction.z);
+        assert!((meet(left) - meet(right)).length() < 1e-12);
+        assert!(meet(left).x.abs() < 1e-12);
+    }
+
+    #[test]
+    fn omnidirectional_eyes_turn_with_the_view() {
+        let camera = Camera::projected(
+            Vector::default(),
+            Vector::new(0.0, 0.0, -1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Arc::new(Equirectangular),
+            2.0,
+            0.0,
+        )
+        .with_stereo(Stereo::new(0.1, f64::INFINITY));
+        // Looking ahead along -z, then to the right along +x.
+        let ahead = camera.view_ray(0.5, 0.5, 0.05, 2.0).unwrap();
+        assert!((ahead.origin - Vector::new(0.05, 0.0, 0.0)).length() < 1e-12);
+        let right = camera.view_ray(0.75, 0.5, 0.05, 2.0).unwrap();
+        assert!((right.direction.to_unit() - Vector::new(1.0, 0.0, 0.0)).length() < 1e-12);
+        assert!((right.origin - Vector::new(0.0, 0.0, 0.05)).length() < 1e-12);
+    }
 }
@@ -1,25 +1,249 @@
+use rand::rngs::SmallRng;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vector::Vector;
 
-pub trait Collidable {
-    fn check_collision(self: &Self, ray: Ray, tmix: f64, tmax: f64) -> Option<Collision>;
+pub trait Collidable: Send + Sync {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>>;
+
+    // Like `check_collision`, but with random numbers for objects that need them to decide
+    // where a ray stops, such as participating media. Surfaces ignore `rng`.
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        _rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        self.check_collision(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self) -> Aabb;
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // Picks a point on the surface for light sampling from `origin`. Shapes that cannot be
+    // sampled return `None` and are only ever found by scattered rays.
+    fn sample_surface(&self, _origin: Vector, _rng: &mut SmallRng) -> Option<SurfaceSample> {
+        None
+    }
+
+    // The solid angle density with which `sample_surface(origin)` picks `collision`, a point
+    // on this surface.
+    fn surface_pdf(&self, _origin: Vector, _collision: &Collision) -> f64 {
+        0.0
+    }
+
+    // The spans of the ray between `tmin` and `tmax` that lie inside this shape, in order.
+    // Only meaningful for closed shapes whose normals point outwards. By default the surface
+    // crossings are found by repeatedly asking for the next collision.
+    fn intervals(&self, ray: Ray, tmin: f64, tmax: f64) -> Vec<Interval<'_>> {
+        let mut intervals = Vec::new();
+        // `Some` while inside, holding where the current span started.
+        let mut entry: Option<Option<Collision>> = None;
+        let mut start = tmin;
+        for crossing in 0..MAX_CROSSINGS {
+            let collision = match self.check_collision(ray, start, tmax) {
+                Some(collision) => collision,
+                None => break,
+            };
+            // Step past the crossing just found so it is not found again.
+            start = collision.time + CROSSING_STEP * collision.time.abs().max(1.0);
+            if collision.normal.dot(ray.direction) < 0.0 {
+                if entry.is_none() {
+                    entry = Some(Some(collision));
+                }
+            } else if let Some(entry) = entry.take() {
+                intervals.push(Interval {
+                    entry,
+                    exit: Some(collision),
+                });
+            } else if crossing == 0 {
+                // Leaving without having entered: the ray started inside.
+                intervals.push(Interval {
+                    entry: None,
+                    exit: Some(collision),
+                });
+            }
+        }
+        if let Some(entry) = entry {
+            intervals.push(Interval { entry, exit: None });
+        }
+        intervals
+    }
+
+    // The fraction of light that gets along the ray from `tmin` to `tmax`, for shadow rays.
+    // Surfaces are opaque, so by default anything in the way blocks it all; participating
+    // media let some through, possibly using `rng` to estimate how much.
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, _rng: &mut SmallRng) -> f64 {
+        if self.check_collision(ray, tmin, tmax).is_some() {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+const MAX_CROSSINGS: usize = 64;
+const CROSSING_STEP: f64 = 1e-9;
+
+// A span of a ray inside a closed shape.
+#[derive(Clone, Copy)]
+pub struct Interval<'a> {
+    // `None` when the ray is already inside at `tmin`.
+    pub entry: Option<Collision<'a>>,
+    // `None` when the ray is still inside at `tmax`.
+    pub exit: Option<Collision<'a>>,
+}
+
+impl<T: Collidable + ?Sized> Collidable for Box<T> {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        (**self).check_collision(ray, tmin, tmax)
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        (**self).sample_collision(ray, tmin, tmax, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        (**self).sample_surface(origin, rng)
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        (**self).surface_pdf(origin, collision)
+    }
+
+    fn intervals(&self, ray: Ray, tmin: f64, tmax: f64) -> Vec<Interval<'_>> {
+        (**self).intervals(ray, tmin, tmax)
+    }
+
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, rng: &mut SmallRng) -> f64 {
+        (**self).transmittance(ray, tmin, tmax, rng)
+    }
 }
 
+impl<T: Collidable + ?Sized> Collidable for Arc<T> {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        (**self).check_collision(ray, tmin, tmax)
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        (**self).sample_collision(ray, tmin, tmax, rng)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        (**self).bounding_box()
+    }
+
+    fn is_emissive(&self) -> bool {
+        (**self).is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        (**self).sample_surface(origin, rng)
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        (**self).surface_pdf(origin, collision)
+    }
+
+    fn intervals(&self, ray: Ray, tmin: f64, tmax: f64) -> Vec<Interval<'_>> {
+        (**self).intervals(ray, tmin, tmax)
+    }
+
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, rng: &mut SmallRng) -> f64 {
+        (**self).transmittance(ray, tmin, tmax, rng)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SurfaceSample {
+    pub point: Vector,
+    pub normal: Vector,
+    pub pdf: f64,
+}
+
+// Converts a density over surface area at `point` into a density over solid angle as seen
+// from `origin`.
+pub fn area_to_solid_angle(area_pdf: f64, origin: Vector, point: Vector, normal: Vector) -> f64 {
+    let offset = point - origin;
+    let distance_squared = offset.squared_length();
+    let cosine = (normal.dot(offset) / distance_squared.sqrt()).abs();
+    if cosine == 0.0 {
+        return 0.0;
+    }
+    area_pdf * distance_squared / cosine
+}
+
+// The inverse of `area_to_solid_angle`.
+pub fn solid_angle_to_area(
+    solid_angle_pdf: f64,
+    origin: Vector,
+    point: Vector,
+    normal: Vector,
+) -> f64 {
+    let offset = point - origin;
+    let distance_squared = offset.squared_length();
+    if distance_squared == 0.0 {
+        return 0.0;
+    }
+    let cosine = (normal.dot(offset) / distance_squared.sqrt()).abs();
+    solid_angle_pdf * cosine / distance_squared
+}
+
+#[derive(Clone, Copy)]
 pub struct Collision<'a> {
     pub time: f64,
     pub point: Vector,
     pub normal: Vector,
+    pub u: f64,
+    pub v: f64,
     pub material: &'a dyn Material,
 }
 
 impl<'a> Collision<'a> {
-    pub fn new(time: f64, point: Vector, normal: Vector, material: &'a dyn Material) -> Collision {
+    pub fn new(
+        time: f64,
+        point: Vector,
+        normal: Vector,
+        material: &'a dyn Material,
+    ) -> Collision<'a> {
         Collision {
             time,
             point,
             normal,
+            u: 0.0,
+            v: 0.0,
             material,
         }
     }
+
+    pub fn with_uv(self, u: f64, v: f64) -> Collision<'a> {
+        Collision { u, v, ..self }
+    }
 }
@@ -10,14 +10,6 @@ pub struct Color {
 }
 
 impl Color {
-    pub fn default() -> Color {
-        Color {
-            red: 0.0,
-            green: 0.0,
-            blue: 0.0,
-        }
-    }
-
     pub fn new(red: f64, green: f64, blue: f64) -> Color {
         Color { red, green, blue }
     }
@@ -30,6 +22,23 @@ impl Color {
         }
     }
 
+    // Decodes 8-bit sRGB-encoded channels into linear color.
+    pub fn from_srgb(red: u8, green: u8, blue: u8) -> Color {
+        let decode = |channel: u8| {
+            let channel = f64::from(channel) / 255.0;
+            if channel <= 0.04045 {
+                channel / 12.92
+            } else {
+                ((channel + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color::new(decode(red), decode(green), decode(blue))
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.red <= 0.0 && self.green <= 0.0 && self.blue <= 0.0
+    }
+
     pub fn antialias(colors: &[Color]) -> Color {
         let mut red: f64 = 0.0;
         let mut green: f64 = 0.0;
@@ -44,11 +53,26 @@ impl Color {
         Color::new(red / len as f64, green / len as f64, blue / len as f64)
     }
 
-    pub fn gamma_correct(&self) -> Color {
+    // Encodes linear channels in [0, 1] with the sRGB transfer curve for display.
+    pub fn to_srgb(self) -> Color {
+        let encode = |channel: f64| {
+            let channel = channel.clamp(0.0, 1.0);
+            if channel <= 0.003_130_8 {
+                channel * 12.92
+            } else {
+                1.055 * channel.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Color::new(encode(self.red), encode(self.green), encode(self.blue))
+    }
+}
+
+impl Default for Color {
+    fn default() -> Color {
         Color {
-            red: self.red.sqrt(),
-            green: self.green.sqrt(),
-            blue: self.blue.sqrt(),
+            red: 0.0,
+            green: 0.0,
+            blue: 0.0,
         }
     }
 }
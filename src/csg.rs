@@ -0,0 +1,272 @@
+use crate::aabb::Aabb;
+use crate::collision::{Collidable, Collision, Interval};
+use crate::ray::Ray;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // Everything in the left shape that is not in the right one.
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// A surface crossing of one of the children, and whether the ray goes into that child there.
+struct Crossing<'a> {
+    collision: Collision<'a>,
+    entering: bool,
+    left: bool,
+}
+
+fn crossings<'a, 'b>(
+    intervals: &'b [Interval<'a>],
+    left: bool,
+) -> impl Iterator<Item = Crossing<'a>> + 'b {
+    intervals.iter().flat_map(move |interval| {
+        let entry = interval.entry.map(|collision| Crossing {
+            collision,
+            entering: true,
+            left,
+        });
+        let exit = interval.exit.map(|collision| Crossing {
+            collision,
+            entering: false,
+            left,
+        });
+        entry.into_iter().chain(exit)
+    })
+}
+
+fn starts_inside(intervals: &[Interval]) -> bool {
+    intervals
+        .first()
+        .is_some_and(|interval| interval.entry.is_none())
+}
+
+// Combines two closed shapes. The surface of the result is made of pieces of the children's
+// surfaces, each keeping its own material; where a piece bounds the result from the other
+// side, such as the inside of the right shape carved out by a difference, its normal is
+// flipped so it still points out of the result.
+pub struct Csg {
+    operation: CsgOperation,
+    left: Box<dyn Collidable>,
+    right: Box<dyn Collidable>,
+    bounding_box: Aabb,
+}
+
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        left: Box<dyn Collidable>,
+        right: Box<dyn Collidable>,
+    ) -> Csg {
+        let (left_bounds, right_bounds) = (left.bounding_box(), right.bounding_box());
+        let bounding_box = match operation {
+            CsgOperation::Union => left_bounds.union(right_bounds),
+            CsgOperation::Intersection => Aabb::new(
+                left_bounds.min.max(right_bounds.min),
+                left_bounds.max.min(right_bounds.max),
+            ),
+            CsgOperation::Difference => left_bounds,
+        };
+        Csg {
+            operation,
+            left,
+            right,
+            bounding_box,
+        }
+    }
+}
+
+impl Collidable for Csg {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let interval = self.intervals(ray, tmin, tmax).into_iter().next()?;
+        interval.entry.or(interval.exit)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn intervals(&self, ray: Ray, tmin: f64, tmax: f64) -> Vec<Interval<'_>> {
+        let left = self.left.intervals(ray, tmin, tmax);
+        if left.is_empty() && self.operation != CsgOperation::Union {
+            return left;
+        }
+        let right = self.right.intervals(ray, tmin, tmax);
+        let mut in_left = starts_inside(&left);
+        let mut in_right = starts_inside(&right);
+
+        let mut crossings: Vec<Crossing> = crossings(&left, true)
+            .chain(crossings(&right, false))
+            .collect();
+        crossings.sort_by(|a, b| {
+            a.collision
+                .time
+                .partial_cmp(&b.collision.time)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mut inside = self.operation.contains(in_left, in_right);
+        // `Some` while inside, holding where the current span started.
+        let mut entry: Option<Option<Collision>> = if inside { Some(None) } else { None };
+        let mut intervals = Vec::new();
+        for crossing in crossings {
+            if crossing.left {
+                in_left = crossing.entering;
+            } else {
+                in_right = crossing.entering;
+            }
+            let now_inside = self.operation.contains(in_left, in_right);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+
+            let mut collision = crossing.collision;
+            if crossing.entering != now_inside {
+                collision.normal = -collision.normal;
+            }
+            if now_inside {
+                entry = Some(Some(collision));
+            } else {
+                intervals.push(Interval {
+                    entry: entry.take().flatten(),
+                    exit: Some(collision),
+                });
+            }
+        }
+        if let Some(entry) = entry {
+            intervals.push(Interval { entry, exit: None });
+        }
+        intervals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+    use crate::shape::AxisAlignedBox;
+    use crate::vector::Vector;
+    use std::f64;
+    use std::sync::Arc;
+
+    // A box spanning `start` to `end` along x, crossed end to end by `ray()`.
+    fn slab(start: f64, end: f64) -> Box<dyn Collidable> {
+        Box::new(AxisAlignedBox::new(
+            Vector::new(start, -1.0, -1.0),
+            Vector::new(end, 1.0, 1.0),
+            Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+        ))
+    }
+
+    // Along x from x = -1, so times are x + 1 and never zero.
+    fn ray() -> Ray {
+        Ray::new(Vector::new(-1.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0)
+    }
+
+    // Each interval as the times of its entry and exit, signed by which way the normal there
+    // faces along x.
+    fn spans(csg: &Csg, tmin: f64) -> Vec<(Option<f64>, Option<f64>)> {
+        let signed = |collision: &Collision| collision.time * collision.normal.x.signum();
+        csg.intervals(ray(), tmin, f64::MAX)
+            .iter()
+            .map(|interval| {
+                (
+                    interval.entry.as_ref().map(signed),
+                    interval.exit.as_ref().map(signed),
+                )
+            })
+            .collect()
+    }
+
+    fn assert_spans(
+        found: Vec<(Option<f64>, Option<f64>)>,
+        expected: &[(Option<f64>, Option<f64>)],
+    ) {
+        let close = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => (a - b).abs() < 1e-9,
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        assert_eq!(found.len(), expected.len(), "{:?} vs {:?}", found, expected);
+        for (found, expected) in found.iter().zip(expected) {
+            assert!(
+                close(found.0, expected.0) && close(found.1, expected.1),
+                "{:?} vs {:?}",
+                found,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn union_merges_overlapping_spans() {
+        let csg = Csg::new(CsgOperation::Union, slab(0.0, 2.0), slab(1.0, 3.0));
+        assert_spans(spans(&csg, 0.0), &[(Some(-1.0), Some(4.0))]);
+    }
+
+    #[test]
+    fn union_keeps_separate_spans_apart() {
+        let csg = Csg::new(CsgOperation::Union, slab(0.0, 1.0), slab(2.0, 3.0));
+        assert_spans(
+            spans(&csg, 0.0),
+            &[(Some(-1.0), Some(2.0)), (Some(-3.0), Some(4.0))],
+        );
+    }
+
+    #[test]
+    fn intersection_keeps_the_overlap() {
+        let csg = Csg::new(CsgOperation::Intersection, slab(0.0, 2.0), slab(1.0, 3.0));
+        assert_spans(spans(&csg, 0.0), &[(Some(-2.0), Some(3.0))]);
+
+        let disjoint = Csg::new(CsgOperation::Intersection, slab(0.0, 1.0), slab(2.0, 3.0));
+        assert_spans(spans(&disjoint, 0.0), &[]);
+    }
+
+    #[test]
+    fn difference_flips_the_carved_surface() {
+        // The right shape's entry becomes the exit, its normal turned to face out of the result.
+        let csg = Csg::new(CsgOperation::Difference, slab(0.0, 2.0), slab(1.0, 3.0));
+        assert_spans(spans(&csg, 0.0), &[(Some(-1.0), Some(2.0))]);
+    }
+
+    #[test]
+    fn difference_splits_around_a_hole() {
+        let csg = Csg::new(CsgOperation::Difference, slab(0.0, 3.0), slab(1.0, 2.0));
+        assert_spans(
+            spans(&csg, 0.0),
+            &[(Some(-1.0), Some(2.0)), (Some(-3.0), Some(4.0))],
+        );
+    }
+
+    #[test]
+    fn spans_can_start_inside() {
+        // Starting at x = 0.5, already inside the left shape.
+        let csg = Csg::new(CsgOperation::Union, slab(0.0, 2.0), slab(1.0, 3.0));
+        assert_spans(spans(&csg, 1.5), &[(None, Some(4.0))]);
+
+        let csg = Csg::new(CsgOperation::Difference, slab(0.0, 3.0), slab(1.0, 2.0));
+        assert_spans(
+            spans(&csg, 1.5),
+            &[(None, Some(2.0)), (Some(-3.0), Some(4.0))],
+        );
+    }
+
+    #[test]
+    fn nearest_collision_can_be_on_the_carved_surface() {
+        let csg = Csg::new(CsgOperation::Difference, slab(0.0, 2.0), slab(-1.0, 1.0));
+        let collision = csg.check_collision(ray(), 0.0, f64::MAX).unwrap();
+        assert!((collision.time - 2.0).abs() < 1e-9);
+        assert!(collision.normal.x < 0.0);
+    }
+}
@@ -0,0 +1,341 @@
+use image::hdr::HDRDecoder;
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::f64;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use crate::color::Color;
+use crate::transform::Transform;
+use crate::vector::Vector;
+
+#[derive(Clone, Copy, Debug)]
+pub struct EnvironmentSample {
+    pub direction: Vector,
+    pub radiance: Color,
+    // Density over solid angle.
+    pub pdf: f64,
+}
+
+// Light arriving from infinitely far away, seen by every ray that leaves the scene.
+pub trait Environment: Send + Sync {
+    // Radiance arriving along the unit `direction`, pointing away from the scene.
+    fn radiance(&self, direction: Vector) -> Color;
+
+    // Picks a direction to sample direct lighting from. Environments that cannot be sampled
+    // return `None` and are only ever found by scattered rays.
+    fn sample(&self, _rng: &mut SmallRng) -> Option<EnvironmentSample> {
+        None
+    }
+
+    // The solid angle density with which `sample` picks `direction`.
+    fn pdf(&self, _direction: Vector) -> f64 {
+        0.0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Constant {
+    color: Color,
+}
+
+impl Constant {
+    pub fn new(color: Color) -> Constant {
+        Constant { color }
+    }
+}
+
+impl Environment for Constant {
+    fn radiance(&self, _direction: Vector) -> Color {
+        self.color
+    }
+}
+
+// Blends from `bottom` straight down to `top` straight up.
+#[derive(Clone, Copy, Debug)]
+pub struct Gradient {
+    bottom: Color,
+    top: Color,
+}
+
+impl Gradient {
+    pub fn new(bottom: Color, top: Color) -> Gradient {
+        Gradient { bottom, top }
+    }
+}
+
+impl Default for Gradient {
+    fn default() -> Gradient {
+        Gradient::new(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0))
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: Vector) -> Color {
+        let t = 0.5 * (direction.y + 1.0);
+        self.bottom * (1.0 - t) + self.top * t
+    }
+}
+
+fn luminance(color: Color) -> f64 {
+    0.2126 * color.red + 0.7152 * color.green + 0.0722 * color.blue
+}
+
+// A latitude-longitude panorama: the top row looks straight up, the bottom row straight down
+// and the center of the image looks along -z. Directions are importance sampled in proportion
+// to texel brightness.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    intensity: f64,
+    rotation: Transform,
+    // Running totals of each row's weight, and of texel weights within every row.
+    row_cdf: Vec<f64>,
+    column_cdfs: Vec<f64>,
+}
+
+impl EnvironmentMap {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> EnvironmentMap {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "environment map has {} pixels for {}x{}",
+            pixels.len(),
+            width,
+            height
+        );
+
+        let mut row_cdf = Vec::with_capacity(height);
+        let mut column_cdfs = Vec::with_capacity(width * height);
+        let mut total = 0.0;
+        for (y, row) in pixels.chunks(width).enumerate() {
+            // Rows near the poles cover less of the sphere than rows at the horizon.
+            let sin_theta = ((y as f64 + 0.5) / height as f64 * f64::consts::PI).sin();
+            let mut row_total = 0.0;
+            for pixel in row {
+                row_total += luminance(*pixel).max(0.0) * sin_theta;
+                column_cdfs.push(row_total);
+            }
+            total += row_total;
+            row_cdf.push(total);
+        }
+
+        EnvironmentMap {
+            width,
+            height,
+            pixels,
+            intensity: 1.0,
+            rotation: Transform::identity(),
+            row_cdf,
+            column_cdfs,
+        }
+    }
+
+    // Loads Radiance `.hdr` files as linear radiance; other formats are decoded as sRGB.
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<EnvironmentMap> {
+        let path = path.as_ref();
+        let is_hdr = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("hdr"));
+
+        if is_hdr {
+            let decoder = HDRDecoder::new(BufReader::new(File::open(path)?))?;
+            let metadata = decoder.metadata();
+            let pixels = decoder
+                .read_image_hdr()?
+                .into_iter()
+                .map(|pixel| {
+                    Color::new(
+                        f64::from(pixel.data[0]),
+                        f64::from(pixel.data[1]),
+                        f64::from(pixel.data[2]),
+                    )
+                })
+                .collect();
+            Ok(EnvironmentMap::new(
+                metadata.width as usize,
+                metadata.height as usize,
+                pixels,
+            ))
+        } else {
+            let image = image::open(path)?.to_rgb();
+            let (width, height) = image.dimensions();
+            let pixels = image
+                .pixels()
+                .map(|pixel| Color::from_srgb(pixel.data[0], pixel.data[1], pixel.data[2]))
+                .collect();
+            Ok(EnvironmentMap::new(width as usize, height as usize, pixels))
+        }
+    }
+
+    pub fn with_intensity(self, intensity: f64) -> EnvironmentMap {
+        EnvironmentMap { intensity, ..self }
+    }
+
+    // Turns the panorama around the vertical axis by `degrees`.
+    pub fn with_rotation(self, degrees: f64) -> EnvironmentMap {
+        EnvironmentMap {
+            rotation: Transform::rotate(Vector::new(0.0, 1.0, 0.0), degrees),
+            ..self
+        }
+    }
+
+    fn total(&self) -> f64 {
+        self.row_cdf.last().cloned().unwrap_or(0.0)
+    }
+
+    // The texel `direction` falls in, and the sine of its polar angle.
+    fn texel(&self, direction: Vector) -> (usize, usize, f64) {
+        let local = self.rotation.inverse().vector(direction);
+        let theta = local.y.clamp(-1.0, 1.0).acos();
+        let phi = local.x.atan2(-local.z);
+        let u = 0.5 + phi / (2.0 * f64::consts::PI);
+        let v = theta / f64::consts::PI;
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        (x, y, theta.sin())
+    }
+
+    fn texel_weight(&self, x: usize, y: usize) -> f64 {
+        let row = &self.column_cdfs[y * self.width..(y + 1) * self.width];
+        row[x] - if x == 0 { 0.0 } else { row[x - 1] }
+    }
+
+    // Converts the probability of picking a texel into a density over solid angle; texels
+    // span 2π/width by π/height of longitude and latitude.
+    fn texel_pdf(&self, x: usize, y: usize, sin_theta: f64) -> f64 {
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let probability = self.texel_weight(x, y) / self.total();
+        probability * (self.width * self.height) as f64
+            / (2.0 * f64::consts::PI * f64::consts::PI * sin_theta)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vector) -> Color {
+        let (x, y, _) = self.texel(direction);
+        self.pixels[y * self.width + x] * self.intensity
+    }
+
+    fn sample(&self, rng: &mut SmallRng) -> Option<EnvironmentSample> {
+        let total = self.total();
+        if total <= 0.0 {
+            return None;
+        }
+        let target = rng.gen::<f64>() * total;
+        let y = self
+            .row_cdf
+            .partition_point(|&running| running <= target)
+            .min(self.height - 1);
+        let row = &self.column_cdfs[y * self.width..(y + 1) * self.width];
+        let target = rng.gen::<f64>() * row[self.width - 1];
+        let x = row
+            .partition_point(|&running| running <= target)
+            .min(self.width - 1);
+
+        let u = (x as f64 + rng.gen::<f64>()) / self.width as f64;
+        let v = (y as f64 + rng.gen::<f64>()) / self.height as f64;
+        let theta = v * f64::consts::PI;
+        let phi = (u - 0.5) * 2.0 * f64::consts::PI;
+        let sin_theta = theta.sin();
+        let local = Vector::new(sin_theta * phi.sin(), theta.cos(), -sin_theta * phi.cos());
+
+        let pdf = self.texel_pdf(x, y, sin_theta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        Some(EnvironmentSample {
+            direction: self.rotation.vector(local),
+            radiance: self.pixels[y * self.width + x] * self.intensity,
+            pdf,
+        })
+    }
+
+    fn pdf(&self, direction: Vector) -> f64 {
+        if self.total() <= 0.0 {
+            return 0.0;
+        }
+        let (x, y, sin_theta) = self.texel(direction);
+        self.texel_pdf(x, y, sin_theta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An 8x4 map with a bright spot, a dark texel and everything in between.
+    fn map() -> EnvironmentMap {
+        let pixels = (0..32)
+            .map(|index| match index {
+                10 => Color::new(20.0, 18.0, 15.0),
+                21 => Color::new(0.0, 0.0, 0.0),
+                _ => Color::new(0.1, 0.2, 0.3) * (1.0 + (index % 5) as f64),
+            })
+            .collect();
+        EnvironmentMap::new(8, 4, pixels).with_rotation(30.0)
+    }
+
+    #[test]
+    fn pdf_matches_samples() {
+        let map = map();
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..1000 {
+            let sample = map.sample(&mut rng).unwrap();
+            let pdf = map.pdf(sample.direction);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-9 * sample.pdf,
+                "{} vs {}",
+                pdf,
+                sample.pdf
+            );
+            let radiance = map.radiance(sample.direction);
+            assert_eq!(radiance.red, sample.radiance.red);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let map = map();
+        // Midpoint rule over polar and azimuthal angles, weighted by sin θ.
+        let (steps_theta, steps_phi) = (400, 800);
+        let (d_theta, d_phi) = (
+            f64::consts::PI / steps_theta as f64,
+            2.0 * f64::consts::PI / steps_phi as f64,
+        );
+        let mut total = 0.0;
+        for i in 0..steps_theta {
+            let theta = (i as f64 + 0.5) * d_theta;
+            for j in 0..steps_phi {
+                let phi = (j as f64 + 0.5) * d_phi;
+                let direction = Vector::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                total += map.pdf(direction) * theta.sin() * d_theta * d_phi;
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-3, "{}", total);
+    }
+
+    #[test]
+    fn black_maps_cannot_be_sampled() {
+        let map = EnvironmentMap::new(2, 2, vec![Color::default(); 4]);
+        assert!(map.sample(&mut SmallRng::seed_from_u64(1)).is_none());
+        assert_eq!(map.pdf(Vector::new(0.0, 1.0, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn gradient_runs_from_bottom_to_top() {
+        let gradient = Gradient::new(Color::new(0.0, 0.0, 0.0), Color::new(1.0, 2.0, 4.0));
+        assert_eq!(gradient.radiance(Vector::new(0.0, -1.0, 0.0)).blue, 0.0);
+        assert_eq!(gradient.radiance(Vector::new(1.0, 0.0, 0.0)).blue, 2.0);
+        assert_eq!(gradient.radiance(Vector::new(0.0, 1.0, 0.0)).blue, 4.0);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::color::Color;
+
+// The pixel buffer a camera renders into, stored row by row from the top. Its width and
+// height are the only record of the image size, so rendering and writing always agree.
+#[derive(Clone, Debug)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Film {
+        assert!(
+            width > 0 && height > 0,
+            "film must be at least one pixel, got {}x{}",
+            width,
+            height
+        );
+        Film {
+            width,
+            height,
+            pixels: vec![Color::default(); width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn rows(&self) -> impl DoubleEndedIterator<Item = &[Color]> {
+        self.pixels.chunks(self.width)
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    pub fn map<F: Fn(Color) -> Color>(&self, f: F) -> Film {
+        Film {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(|&pixel| f(pixel)).collect(),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::vector::Vector;
+
+#[derive(Debug)]
+pub enum GridError {
+    Io(PathBuf, io::Error),
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for GridError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GridError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            GridError::Invalid {
+                path,
+                line,
+                message,
+            } => write!(f, "{}, line {}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for GridError {}
+
+// Densities sampled on a regular lattice of voxels, filling the unit cube. Each value sits at
+// the center of its voxel and densities in between are interpolated trilinearly.
+pub struct DensityGrid {
+    width: usize,
+    height: usize,
+    depth: usize,
+    // x varies fastest, then y, then z.
+    values: Vec<f64>,
+    max: f64,
+}
+
+impl DensityGrid {
+    pub fn new(width: usize, height: usize, depth: usize, values: Vec<f64>) -> DensityGrid {
+        assert!(
+            width > 0 && height > 0 && depth > 0,
+            "density grid must not be empty"
+        );
+        assert_eq!(
+            values.len(),
+            width * height * depth,
+            "density grid has {} values for {}x{}x{}",
+            values.len(),
+            width,
+            height,
+            depth
+        );
+        let max = values.iter().cloned().fold(0.0, f64::max);
+        DensityGrid {
+            width,
+            height,
+            depth,
+            values,
+            max,
+        }
+    }
+
+    // Reads a plain text grid: the width, height and depth, followed by that many densities
+    // in the same order as `values`. Whitespace and line breaks are interchangeable and `#`
+    // starts a comment.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<DensityGrid, GridError> {
+        let path = path.as_ref();
+        let source =
+            fs::read_to_string(path).map_err(|error| GridError::Io(path.to_path_buf(), error))?;
+        let error = |line: usize, message: String| GridError::Invalid {
+            path: path.to_path_buf(),
+            line,
+            message,
+        };
+
+        let mut tokens = source.lines().enumerate().flat_map(|(index, line)| {
+            let line_number = index + 1;
+            let content = line.split('#').next().unwrap_or("");
+            content
+                .split_whitespace()
+                .map(move |token| (line_number, token))
+        });
+
+        let mut dimensions: [usize; 3] = [0; 3];
+        let mut header_line = 1;
+        for (dimension, name) in dimensions.iter_mut().zip(&["width", "height", "depth"]) {
+            let (line, token) = tokens
+                .next()
+                .ok_or_else(|| error(1, format!("missing grid {}", name)))?;
+            *dimension = token
+                .parse()
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| error(line, format!("invalid grid {} `{}`", name, token)))?;
+            header_line = line;
+        }
+        let [width, height, depth] = dimensions;
+
+        // The header is not trusted to size anything until the densities are all read.
+        let count = width
+            .checked_mul(height)
+            .and_then(|area| area.checked_mul(depth))
+            .ok_or_else(|| {
+                error(
+                    header_line,
+                    format!("grid {}x{}x{} is too large", width, height, depth),
+                )
+            })?;
+        let mut values = Vec::new();
+        let mut last_line = 1;
+        for (line, token) in tokens {
+            last_line = line;
+            let value: f64 = token
+                .parse()
+                .map_err(|_| error(line, format!("invalid density `{}`", token)))?;
+            if !value.is_finite() || value < 0.0 {
+                return Err(error(
+                    line,
+                    format!("density must be finite and not negative, got {}", value),
+                ));
+            }
+            values.push(value);
+        }
+        if values.len() != count {
+            return Err(error(
+                last_line,
+                format!(
+                    "expected {} densities for a {}x{}x{} grid, found {}",
+                    count,
+                    width,
+                    height,
+                    depth,
+                    values.len()
+                ),
+            ));
+        }
+        Ok(DensityGrid::new(width, height, depth, values))
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f64 {
+        self.values[(z * self.height + y) * self.width + x]
+    }
+
+    // The density at `point` in the unit cube; points outside it are empty.
+    pub fn density(&self, point: Vector) -> f64 {
+        if (0..3).any(|axis| !(0.0..=1.0).contains(&point[axis])) {
+            return 0.0;
+        }
+
+        // The two voxels on either side along one axis, and how far between them `position`
+        // lies. Beyond the outermost voxel centers the edge value is held.
+        let neighbours = |position: f64, size: usize| {
+            let continuous = (position * size as f64 - 0.5).clamp(0.0, (size - 1) as f64);
+            let low = (continuous as usize).min(size - 1);
+            (low, (low + 1).min(size - 1), continuous - low as f64)
+        };
+        let (x0, x1, fx) = neighbours(point.x, self.width);
+        let (y0, y1, fy) = neighbours(point.y, self.height);
+        let (z0, z1, fz) = neighbours(point.z, self.depth);
+
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let plane = |z: usize| {
+            lerp(
+                lerp(self.value(x0, y0, z), self.value(x1, y0, z), fx),
+                lerp(self.value(x0, y1, z), self.value(x1, y1, z), fx),
+                fy,
+            )
+        };
+        lerp(plane(z0), plane(z1), fz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn load(name: &str, source: &str) -> Result<DensityGrid, GridError> {
+        let path = std::env::temp_dir().join(format!("man-ray-{}-{}.grid", process::id(), name));
+        fs::write(&path, source).unwrap();
+        let grid = DensityGrid::load(&path);
+        fs::remove_file(&path).unwrap();
+        grid
+    }
+
+    fn invalid(name: &str, source: &str) -> (usize, String) {
+        match load(name, source) {
+            Err(GridError::Invalid { line, message, .. }) => (line, message),
+            Err(error) => panic!("expected an invalid grid, got {}", error),
+            Ok(_) => panic!("expected an invalid grid, but it loaded"),
+        }
+    }
+
+    #[test]
+    fn loads_values_with_comments() {
+        let grid = load("valid", "# a 2x1x1 grid\n2 1 1\n0.5 # left\n1.5\n").unwrap();
+        assert_eq!(grid.max(), 1.5);
+        assert_eq!(grid.density(Vector::new(0.25, 0.5, 0.5)), 0.5);
+    }
+
+    #[test]
+    fn reports_load_errors() {
+        assert_eq!(
+            invalid("empty", "# nothing\n"),
+            (1, String::from("missing grid width"))
+        );
+        assert_eq!(
+            invalid("zero", "2\n0 1\n"),
+            (2, String::from("invalid grid height `0`"))
+        );
+        assert_eq!(
+            invalid("density", "1 1 2\n0.5\n-1\n"),
+            (
+                3,
+                String::from("density must be finite and not negative, got -1")
+            )
+        );
+        assert_eq!(
+            invalid("short", "2 2 1\n1 2\n3\n"),
+            (
+                3,
+                String::from("expected 4 densities for a 2x2x1 grid, found 3")
+            )
+        );
+        assert_eq!(
+            invalid("word", "1 1 1\nthick\n"),
+            (2, String::from("invalid density `thick`"))
+        );
+    }
+
+    #[test]
+    fn reports_oversized_grids() {
+        let size = usize::MAX / 2;
+        let (line, message) = invalid("huge", &format!("{} 2\n2\n1\n", size));
+        assert_eq!(line, 2);
+        assert!(message.contains("too large"), "{}", message);
+
+        // Large enough to abort if allocated up front, yet not overflowing.
+        let (_, message) = invalid("large", "100000 100000 100000\n1\n");
+        assert!(message.contains("found 1"), "{}", message);
+    }
+
+    #[test]
+    fn interpolates_trilinearly() {
+        // Corners of a 2x2x2 grid hold x + 2y + 4z.
+        let grid = DensityGrid::new(2, 2, 2, (0..8).map(f64::from).collect());
+        let at = |x: f64, y: f64, z: f64| grid.density(Vector::new(x, y, z));
+
+        // Voxel centers hold their own values.
+        assert_eq!(at(0.25, 0.25, 0.25), 0.0);
+        assert_eq!(at(0.75, 0.25, 0.25), 1.0);
+        assert_eq!(at(0.25, 0.75, 0.75), 6.0);
+        // Halfway between centers on every axis, the mean of all eight.
+        assert!((at(0.5, 0.5, 0.5) - 3.5).abs() < 1e-12);
+        assert!((at(0.375, 0.5, 0.625) - (0.25 + 1.0 + 3.0)).abs() < 1e-12);
+        // Beyond the outermost centers the edge value is held, and outside the cube is empty.
+        assert_eq!(at(0.0, 0.0, 0.0), 0.0);
+        assert_eq!(at(1.0, 1.0, 1.0), 7.0);
+        assert_eq!(at(1.5, 0.5, 0.5), 0.0);
+        assert_eq!(at(0.5, -0.1, 0.5), 0.0);
+    }
+}
@@ -1,8 +1,11 @@
+use image::hdr::HDREncoder;
 use image::{ImageBuffer, Rgb, RgbImage};
-use std::io;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
 
 use crate::color::Color;
+use crate::film::Film;
 
 impl From<&Color> for Rgb<u8> {
     fn from(color: &Color) -> Self {
@@ -16,16 +19,266 @@ impl From<&Color> for Rgb<u8> {
     }
 }
 
-pub fn write_image(pixels: &[Color], aspect: f64, scale: usize, filename: &str) -> io::Result<()> {
-    let width = scale;
-    let height = (scale as f64 / aspect) as usize;
+impl From<&Color> for Rgb<f32> {
+    fn from(color: &Color) -> Self {
+        Self {
+            data: [color.red as f32, color.green as f32, color.blue as f32],
+        }
+    }
+}
+
+fn extension(filename: &str) -> Option<String> {
+    Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+// Whether `filename` names a floating point format that should be given linear values rather
+// than tone mapped display colors.
+pub fn is_hdr(filename: &str) -> bool {
+    matches!(extension(filename).as_deref(), Some("exr" | "hdr" | "pfm"))
+}
+
+// Writes `film` in the format named by the file extension: `.exr`, `.hdr` and `.pfm` store the
+// values unchanged, anything else goes through the `image` crate as 8-bit color and expects
+// display values in [0, 1].
+pub fn write_image(film: &Film, filename: &str) -> io::Result<()> {
+    let path = Path::new(filename);
+    match extension(filename).as_deref() {
+        Some("exr") => write_exr(film, path),
+        Some("hdr") => write_hdr(film, path),
+        Some("pfm") => write_pfm(film, path),
+        _ => {
+            let mut img: RgbImage = ImageBuffer::new(film.width() as u32, film.height() as u32);
+            for (y, row) in film.rows().enumerate() {
+                for (x, pixel) in row.iter().enumerate() {
+                    img.put_pixel(x as u32, y as u32, pixel.into());
+                }
+            }
+            img.save(path)
+        }
+    }
+}
+
+fn create(path: &Path) -> io::Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+fn write_hdr(film: &Film, path: &Path) -> io::Result<()> {
+    let data: Vec<Rgb<f32>> = film.pixels().iter().map(Rgb::from).collect();
+    HDREncoder::new(create(path)?).encode(&data, film.width(), film.height())
+}
+
+// Portable float map: a short text header followed by little-endian 32-bit floats, with rows
+// stored from the bottom of the image up.
+fn write_pfm(film: &Film, path: &Path) -> io::Result<()> {
+    let mut file = create(path)?;
+    write!(file, "PF\n{} {}\n-1.0\n", film.width(), film.height())?;
+    for row in film.rows().rev() {
+        for pixel in row {
+            for channel in &[pixel.red, pixel.green, pixel.blue] {
+                file.write_all(&(*channel as f32).to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+const EXR_FLOAT: i32 = 2;
+
+fn exr_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
+
+// A single-part, uncompressed scanline OpenEXR file with 32-bit float R, G and B channels.
+fn write_exr(film: &Film, path: &Path) -> io::Result<()> {
+    let (width, height) = (film.width(), film.height());
+    // Channels must be listed, and stored within each scanline, in alphabetical order.
+    let channels = ["B", "G", "R"];
+    let mut channel_list = Vec::new();
+    for name in &channels {
+        channel_list.extend_from_slice(name.as_bytes());
+        channel_list.push(0);
+        channel_list.extend_from_slice(&EXR_FLOAT.to_le_bytes());
+        // pLinear and three reserved bytes, then the x and y sampling rates.
+        channel_list.extend_from_slice(&[0, 0, 0, 0]);
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+        channel_list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channel_list.push(0);
+
+    let mut window = Vec::new();
+    for value in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    header.extend_from_slice(&2i32.to_le_bytes());
+    exr_attribute(&mut header, "channels", "chlist", &channel_list);
+    exr_attribute(&mut header, "compression", "compression", &[0]);
+    exr_attribute(&mut header, "dataWindow", "box2i", &window);
+    exr_attribute(&mut header, "displayWindow", "box2i", &window);
+    exr_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Every scanline is its own chunk: its y coordinate, byte count and channel data.
+    let line_size = width * channels.len() * 4;
+    let chunk_size = 8 + line_size;
+    let first_chunk = header.len() + height * 8;
 
-    let mut img: RgbImage = ImageBuffer::new(width as u32, height as u32);
+    let mut file = create(path)?;
+    file.write_all(&header)?;
+    for y in 0..height {
+        file.write_all(&((first_chunk + y * chunk_size) as u64).to_le_bytes())?;
+    }
+    for (y, row) in film.rows().enumerate() {
+        file.write_all(&(y as i32).to_le_bytes())?;
+        file.write_all(&(line_size as i32).to_le_bytes())?;
+        let planes: [fn(&Color) -> f64; 3] =
+            [|pixel| pixel.blue, |pixel| pixel.green, |pixel| pixel.red];
+        for channel in &planes {
+            for pixel in row {
+                file.write_all(&(channel(pixel) as f32).to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    // A 3x2 film whose every channel is different, with values outside [0, 1].
+    fn film() -> Film {
+        let mut film = Film::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                let base = (y * 3 + x) as f64;
+                film.set(x, y, Color::new(base * 2.5, -base, base + 0.125));
+            }
+        }
+        film
+    }
+
+    fn written(film: &Film, name: &str) -> Vec<u8> {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("man-ray-{}-{}", process::id(), name));
+        write_image(film, path.to_str().unwrap()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    fn float(bytes: &[u8]) -> f64 {
+        f64::from(f32::from_le_bytes(bytes[..4].try_into().unwrap()))
+    }
+
+    fn int(bytes: &[u8]) -> usize {
+        i32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize
+    }
+
+    fn assert_same(film: &Film, pixels: &[Color]) {
+        assert_eq!(pixels.len(), film.pixels().len());
+        for (read, expected) in pixels.iter().zip(film.pixels()) {
+            assert_eq!(
+                (read.red, read.green, read.blue),
+                (expected.red, expected.green, expected.blue)
+            );
+        }
+    }
+
+    #[test]
+    fn detects_hdr_extensions() {
+        assert!(is_hdr("out.exr"));
+        assert!(is_hdr("OUT.PFM"));
+        assert!(is_hdr("dir/out.hdr"));
+        assert!(!is_hdr("out.png"));
+        assert!(!is_hdr("exr"));
+    }
+
+    #[test]
+    fn pfm_round_trips() {
+        let film = film();
+        let bytes = written(&film, "round-trip.pfm");
+
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let data = &bytes[header.len()..];
+        assert_eq!(data.len(), 3 * 2 * 3 * 4);
+
+        // Rows are stored bottom up.
+        let mut pixels = Vec::new();
+        for row in data.chunks(3 * 3 * 4).rev() {
+            for pixel in row.chunks(3 * 4) {
+                pixels.push(Color::new(
+                    float(&pixel[0..]),
+                    float(&pixel[4..]),
+                    float(&pixel[8..]),
+                ));
+            }
+        }
+        assert_same(&film, &pixels);
+    }
+
+    #[test]
+    fn exr_round_trips() {
+        let film = film();
+        let bytes = written(&film, "round-trip.exr");
+
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        assert_eq!(int(&bytes[4..]), 2);
+
+        // Walk the attributes up to the empty name that ends the header.
+        let mut position = 8;
+        let mut names = Vec::new();
+        while bytes[position] != 0 {
+            let mut fields = bytes[position..].splitn(3, |&byte| byte == 0);
+            let name = fields.next().unwrap();
+            let kind = fields.next().unwrap();
+            names.push(String::from_utf8(name.to_vec()).unwrap());
+            position += name.len() + kind.len() + 2;
+            position += 4 + int(&bytes[position..]);
+        }
+        position += 1;
+        assert!(names.iter().any(|name| name == "channels"));
+        assert!(names.iter().any(|name| name == "dataWindow"));
 
-    for (y, row) in pixels.chunks(width).enumerate() {
-        for (x, pixel) in row.iter().enumerate() {
-            img.put_pixel(x as u32, y as u32, pixel.into());
+        let mut pixels = Vec::new();
+        for y in 0..2 {
+            let offset = u64::from_le_bytes(bytes[position + y * 8..][..8].try_into().unwrap());
+            let chunk = &bytes[offset as usize..];
+            assert_eq!(int(chunk), y);
+            assert_eq!(int(&chunk[4..]), 3 * 3 * 4);
+            // Each scanline holds whole planes of B, then G, then R.
+            let plane = |channel: usize, x: usize| float(&chunk[8 + (channel * 3 + x) * 4..]);
+            for x in 0..3 {
+                pixels.push(Color::new(plane(2, x), plane(1, x), plane(0, x)));
+            }
         }
+        assert_same(&film, &pixels);
     }
-    img.save(Path::new(filename))
 }
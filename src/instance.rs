@@ -0,0 +1,254 @@
+use rand::rngs::SmallRng;
+
+use crate::aabb::Aabb;
+use crate::collision::{
+    area_to_solid_angle, solid_angle_to_area, Collidable, Collision, Interval, SurfaceSample,
+};
+use crate::ray::Ray;
+use crate::transform::{Placement, Transform};
+use crate::vector::Vector;
+
+// Movement between two placements over a span of time, holding still before and after.
+#[derive(Clone, Copy, Debug)]
+struct Motion {
+    start: Placement,
+    end: Placement,
+    start_time: f64,
+    end_time: f64,
+}
+
+impl Motion {
+    fn placement(&self, time: f64) -> Placement {
+        if self.end_time <= self.start_time {
+            return self.start;
+        }
+        let t = ((time - self.start_time) / (self.end_time - self.start_time)).clamp(0.0, 1.0);
+        self.start.lerp(&self.end, t)
+    }
+}
+
+const MOTION_STEPS: usize = 64;
+
+// Bounds of `bounds` over the whole motion. The box's corners are followed in small steps,
+// and each step's bounds are padded by the furthest any corner moved during it, which covers
+// the curved paths rotation takes them on between steps.
+fn swept_bounds(bounds: Aabb, motion: &Motion) -> Aabb {
+    if !bounds.is_finite() {
+        return Aabb::infinite();
+    }
+    let corners: Vec<Vector> = (0..8)
+        .map(|corner| {
+            Vector::new(
+                if corner & 1 == 0 {
+                    bounds.min.x
+                } else {
+                    bounds.max.x
+                },
+                if corner & 2 == 0 {
+                    bounds.min.y
+                } else {
+                    bounds.max.y
+                },
+                if corner & 4 == 0 {
+                    bounds.min.z
+                } else {
+                    bounds.max.z
+                },
+            )
+        })
+        .collect();
+    let at = |step: usize| {
+        let placement = motion
+            .start
+            .lerp(&motion.end, step as f64 / MOTION_STEPS as f64);
+        let transform = placement.transform();
+        corners
+            .iter()
+            .map(|&corner| transform.point(corner))
+            .collect::<Vec<_>>()
+    };
+
+    let mut previous = at(0);
+    let mut swept = Aabb::from_points(&previous);
+    let mut largest_step: f64 = 0.0;
+    for step in 1..=MOTION_STEPS {
+        let current = at(step);
+        for (before, after) in previous.iter().zip(&current) {
+            largest_step = largest_step.max((*after - *before).length());
+        }
+        swept = swept.union(Aabb::from_points(&current));
+        previous = current;
+    }
+    let padding = Vector::new(largest_step, largest_step, largest_step);
+    Aabb::new(swept.min - padding, swept.max + padding)
+}
+
+// Places `object` in the world through `transform`. Rays are moved into the object's own
+// space instead of moving the object, so wrapping an `Arc` of a mesh in many `Transformed`s
+// instances it without copying any geometry.
+pub struct Transformed<T: Collidable> {
+    object: T,
+    transform: Transform,
+    inverse: Transform,
+    motion: Option<Motion>,
+    bounding_box: Aabb,
+}
+
+impl<T: Collidable> Transformed<T> {
+    pub fn new(object: T, transform: Transform) -> Transformed<T> {
+        let bounding_box = transform.bounding_box(object.bounding_box());
+        Transformed {
+            object,
+            transform,
+            inverse: transform.inverse(),
+            motion: None,
+            bounding_box,
+        }
+    }
+
+    // Moves `object` from `start` at `start_time` to `end` at `end_time`, for motion blur.
+    // Animated objects are only found by scattered rays when emissive, since light samples
+    // are not drawn at any particular time.
+    pub fn animated(
+        object: T,
+        start: Placement,
+        end: Placement,
+        start_time: f64,
+        end_time: f64,
+    ) -> Transformed<T> {
+        let motion = Motion {
+            start,
+            end,
+            start_time,
+            end_time,
+        };
+        let transform = start.transform();
+        Transformed {
+            bounding_box: swept_bounds(object.bounding_box(), &motion),
+            object,
+            transform,
+            inverse: transform.inverse(),
+            motion: Some(motion),
+        }
+    }
+
+    pub fn object(&self) -> &T {
+        &self.object
+    }
+
+    // The transform at the start of any motion.
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    // The transform at `time`, and its inverse.
+    fn transforms_at(&self, time: f64) -> (Transform, Transform) {
+        match &self.motion {
+            Some(motion) => {
+                let transform = motion.placement(time).transform();
+                (transform, transform.inverse())
+            }
+            None => (self.transform, self.inverse),
+        }
+    }
+}
+
+// Moves a collision the object reported for the object space version of `ray` back out to
+// world space.
+fn world_collision<'a>(transform: Transform, ray: Ray, collision: Collision<'a>) -> Collision<'a> {
+    Collision {
+        point: ray.point_at_parameter(collision.time),
+        normal: transform.normal(collision.normal).to_unit(),
+        ..collision
+    }
+}
+
+impl<T: Collidable> Collidable for Transformed<T> {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let (transform, inverse) = self.transforms_at(ray.time);
+        let collision = self.object.check_collision(inverse.ray(ray), tmin, tmax)?;
+        Some(world_collision(transform, ray, collision))
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        let (transform, inverse) = self.transforms_at(ray.time);
+        let collision = self
+            .object
+            .sample_collision(inverse.ray(ray), tmin, tmax, rng)?;
+        Some(world_collision(transform, ray, collision))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    // Light samples are drawn in object space. Their densities are converted through surface
+    // area, which is what a non-rigid transform actually stretches.
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        if self.motion.is_some() {
+            return None;
+        }
+        let local_origin = self.inverse.point(origin);
+        let sample = self.object.sample_surface(local_origin, rng)?;
+        let local_area_pdf =
+            solid_angle_to_area(sample.pdf, local_origin, sample.point, sample.normal);
+
+        let point = self.transform.point(sample.point);
+        let normal = self.transform.normal(sample.normal).to_unit();
+        let area_pdf = local_area_pdf / self.transform.area_scale(sample.normal);
+        Some(SurfaceSample {
+            point,
+            normal,
+            pdf: area_to_solid_angle(area_pdf, origin, point, normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        if self.motion.is_some() {
+            return 0.0;
+        }
+        let local_origin = self.inverse.point(origin);
+        let local = Collision {
+            point: self.inverse.point(collision.point),
+            normal: self.inverse.normal(collision.normal).to_unit(),
+            ..*collision
+        };
+        let local_pdf = self.object.surface_pdf(local_origin, &local);
+        let local_area_pdf =
+            solid_angle_to_area(local_pdf, local_origin, local.point, local.normal);
+
+        let area_pdf = local_area_pdf / self.transform.area_scale(local.normal);
+        area_to_solid_angle(area_pdf, origin, collision.point, collision.normal)
+    }
+
+    fn intervals(&self, ray: Ray, tmin: f64, tmax: f64) -> Vec<Interval<'_>> {
+        let (transform, inverse) = self.transforms_at(ray.time);
+        self.object
+            .intervals(inverse.ray(ray), tmin, tmax)
+            .into_iter()
+            .map(|interval| Interval {
+                entry: interval
+                    .entry
+                    .map(|collision| world_collision(transform, ray, collision)),
+                exit: interval
+                    .exit
+                    .map(|collision| world_collision(transform, ray, collision)),
+            })
+            .collect()
+    }
+
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, rng: &mut SmallRng) -> f64 {
+        let (_, inverse) = self.transforms_at(ray.time);
+        self.object.transmittance(inverse.ray(ray), tmin, tmax, rng)
+    }
+}
@@ -1,9 +1,30 @@
+pub mod aabb;
+pub mod aperture;
+pub mod bvh;
 pub mod camera;
 pub mod collision;
 pub mod color;
+pub mod csg;
+pub mod environment;
+pub mod film;
+pub mod grid;
 pub mod image;
+pub mod instance;
+pub mod light;
 pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod projection;
+pub mod quadric;
 pub mod ray;
+pub mod scene;
+pub mod settings;
 pub mod shape;
+pub mod sky;
+pub mod stereo;
+pub mod texture;
+pub mod tonemap;
+pub mod transform;
 pub mod vector;
+pub mod volume;
 pub mod world;
@@ -0,0 +1,52 @@
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::f64;
+
+use crate::color::Color;
+use crate::vector::Vector;
+
+// A light infinitely far away, such as the sun, delivering `irradiance` to a surface facing
+// it. It is only ever reached by shadow rays; a non-zero angular radius spreads it over a
+// small cone of directions to soften its shadows.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectionalLight {
+    direction: Vector,
+    irradiance: Color,
+    cos_max: f64,
+}
+
+impl DirectionalLight {
+    // `direction` points from the scene towards the light.
+    pub fn new(direction: Vector, irradiance: Color) -> DirectionalLight {
+        DirectionalLight {
+            direction: direction.to_unit(),
+            irradiance,
+            cos_max: 1.0,
+        }
+    }
+
+    pub fn with_angular_radius(self, degrees: f64) -> DirectionalLight {
+        DirectionalLight {
+            cos_max: degrees.to_radians().cos(),
+            ..self
+        }
+    }
+
+    pub fn irradiance(&self) -> Color {
+        self.irradiance
+    }
+
+    // A direction towards the light, uniformly distributed over its disc.
+    pub fn sample(&self, rng: &mut SmallRng) -> Vector {
+        if self.cos_max >= 1.0 {
+            return self.direction;
+        }
+        let cos_theta = 1.0 - rng.gen::<f64>() * (1.0 - self.cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = rng.gen::<f64>() * 2.0 * f64::consts::PI;
+        let (tangent, bitangent) = self.direction.basis();
+        tangent * (sin_theta * phi.cos())
+            + bitangent * (sin_theta * phi.sin())
+            + self.direction * cos_theta
+    }
+}
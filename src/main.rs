@@ -1,91 +1,132 @@
-use std::io;
-
-use itertools::iproduct;
-use man_ray::camera::Camera;
-use man_ray::collision::Collidable;
-use man_ray::image::write_image;
-use man_ray::material::{Dialectic, Lambertian, Material, Metal};
-use man_ray::shape::Sphere;
-use man_ray::vector::Vector;
-use man_ray::world::World;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+
+use clap::builder::RangedU64ValueParser;
+use clap::Parser;
+use man_ray::film::Film;
+use man_ray::image::{is_hdr, write_image};
+use man_ray::scene::Scene;
+use man_ray::settings::RenderSettings;
+use man_ray::tonemap::{PostProcess, ToneMap};
 use rand::prelude::*;
-use rand::rngs::SmallRng;
 use rand::thread_rng;
 
-fn main() -> io::Result<()> {
-    let n = 1;
-    let ns = (n as f64).sqrt() as isize;
-    let mut objects: Vec<Box<dyn Collidable>> = Vec::with_capacity(n);
-
-    let ground = Box::new(Sphere::new(
-        Vector::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Box::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
-    ));
-    let dialectic = Box::new(Sphere::new(
-        Vector::new(0.0, 1.0, 0.0),
-        1.0,
-        Box::new(Dialectic::new(1.5)),
-    ));
-    let lambertian = Box::new(Sphere::new(
-        Vector::new(-4.0, 1.0, 0.0),
-        1.0,
-        Box::new(Lambertian::new(Vector::new(0.4, 0.2, 0.1))),
-    ));
-    let metal = Box::new(Sphere::new(
-        Vector::new(4.0, 1.0, 0.0),
-        1.0,
-        Box::new(Metal::new(Vector::new(0.7, 0.6, 0.5), 0.0)),
-    ));
-    objects.push(ground);
-    objects.push(dialectic);
-    objects.push(lambertian);
-    objects.push(metal);
-
-    let mut rng = SmallRng::from_rng(thread_rng())?;
-    for (i, e) in iproduct!(-ns..ns, -ns..ns) {
-        let choose_mat = rng.gen::<f64>();
-        let center = Vector::new(
-            i as f64 + 0.9 + rng.gen::<f64>(),
-            0.2,
-            e as f64 + 0.9 * rng.gen::<f64>(),
-        );
-        let material: Box<dyn Material> = if (center - Vector::new(4.0, 0.2, 0.0)).length() > 0.9 {
-            Box::new(Lambertian::new(Vector::new(
-                rng.gen::<f64>() * rng.gen::<f64>(),
-                rng.gen::<f64>() * rng.gen::<f64>(),
-                rng.gen::<f64>() * rng.gen::<f64>(),
-            )))
-        } else if choose_mat < 0.95 {
-            Box::new(Metal::new(
-                Vector::new(
-                    0.5 * (1.0 + rng.gen::<f64>()),
-                    0.5 * (1.0 + rng.gen::<f64>()),
-                    0.5 * (1.0 + rng.gen::<f64>()),
-                ),
-                0.5 * rng.gen::<f64>(),
-            ))
-        } else {
-            Box::new(Dialectic::new(1.5))
-        };
-        objects.push(Box::new(Sphere::new(center, 0.2, material)));
+#[derive(Parser, Debug)]
+#[command(name = "man-ray", about = "Render a scene description to an image")]
+struct Options {
+    /// Scene description file to render
+    scene: PathBuf,
+
+    /// Where to write the rendered image; `.exr`, `.hdr` and `.pfm` keep linear HDR values
+    #[arg(short, long, default_value = "example.png")]
+    output: String,
+
+    /// Image width in pixels, for each eye of stereo scenes
+    #[arg(short = 'W', long, default_value_t = 400)]
+    width: usize,
+
+    /// Image height in pixels, for each eye of stereo scenes; derived from the width and aspect ratio when omitted
+    #[arg(short = 'H', long)]
+    height: Option<usize>,
+
+    /// Aspect ratio to derive the height from, instead of the scene's own ratio
+    #[arg(short, long, conflicts_with = "height")]
+    aspect: Option<f64>,
+
+    /// Start from fast, noisy preview settings instead of final-quality ones
+    #[arg(short, long)]
+    preview: bool,
+
+    /// Samples per pixel [default: 100, or 4 with --preview]
+    #[arg(short, long, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    samples: Option<usize>,
+
+    /// Maximum number of bounces per path [default: 50, or 8 with --preview]
+    #[arg(short, long)]
+    depth: Option<usize>,
+
+    /// Seed for the random number generators; random when omitted
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Exposure adjustment in stops applied before tone mapping
+    #[arg(short, long, default_value_t = 0.0, allow_negative_numbers = true)]
+    exposure: f64,
+
+    /// Tone mapping operator for 8-bit output: clamp, reinhard or aces
+    #[arg(long, default_value_t = ToneMap::Clamp)]
+    tone_map: ToneMap,
+
+    /// Number of render threads; defaults to one per core
+    #[arg(short, long)]
+    threads: Option<usize>,
+}
+
+fn run(options: Options) -> Result<(), Box<dyn Error>> {
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    let Scene {
+        camera,
+        world,
+        environment,
+    } = Scene::load(&options.scene)
+        .map_err(|error| format!("{}: {}", options.scene.display(), error))?;
+
+    let width = options.width;
+    let height = match options.height {
+        Some(height) => height,
+        None => {
+            let aspect = options.aspect.unwrap_or_else(|| camera.aspect());
+            if !(aspect > 0.0 && aspect.is_finite()) {
+                return Err(format!("invalid aspect ratio {}", aspect).into());
+            }
+            (width as f64 / aspect).round() as usize
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err(format!("invalid output size {}x{}", width, height).into());
     }
 
-    let origin = Vector::new(8.0, 2.0, 3.0);
-    let target = Vector::new(0.0, 1.0, 0.0);
-    let vup = Vector::new(0.0, 1.0, 0.0);
-    let field_of_view = 33.0;
-    let aspect_ratio = 4.0 / 3.0;
-    let apurture = 0.0;
-    let camera = Camera::new(origin, target, vup, field_of_view, aspect_ratio, apurture);
-    let world = World::new(objects);
-
-    let scale = 30;
-    write_image(
-        camera.render(&world, scale, &mut rng).as_ref(),
-        aspect_ratio,
-        scale,
-        "example.png",
-    )?;
+    let seed = options.seed.unwrap_or_else(|| thread_rng().gen());
+    let mut settings = if options.preview {
+        RenderSettings::preview()
+    } else {
+        RenderSettings::default()
+    };
+    settings.environment = environment;
+    if let Some(samples) = options.samples {
+        settings.samples = samples;
+    }
+    if let Some(depth) = options.depth {
+        settings.max_depth = depth;
+    }
+
+    let post = PostProcess {
+        exposure: options.exposure,
+        tone_map: options.tone_map,
+    };
+    let (frame_width, frame_height) = camera.frame_size(width, height);
+    let mut film = Film::new(frame_width, frame_height);
+    camera.render(&world, &mut film, &settings, seed);
+    // Floating point formats keep linear radiance for grading downstream, so only exposure
+    // applies to them.
+    let film = if is_hdr(&options.output) {
+        film.map(|pixel| post.expose(pixel))
+    } else {
+        film.map(|pixel| post.apply(pixel))
+    };
+    write_image(&film, &options.output)?;
     Ok(())
 }
+
+fn main() {
+    if let Err(error) = run(Options::parse()) {
+        eprintln!("man-ray: {}", error);
+        process::exit(1);
+    }
+}
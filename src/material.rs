@@ -1,23 +1,54 @@
-use crate::camera::random_in_unit_sphere;
+use crate::camera::{random_in_unit_sphere, random_unit_vector};
 use crate::collision::Collision;
+use crate::color::Color;
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
 use crate::vector::Vector;
 use rand::prelude::*;
 use rand::rngs::SmallRng;
+use std::f64;
+use std::sync::Arc;
 
-pub trait Material {
-    fn scatter(
-        self: &Self,
-        ray: Ray,
-        collision: Collision,
-        rng: &mut SmallRng,
-    ) -> Option<MaterialEffect>;
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray: Ray, collision: Collision, rng: &mut SmallRng)
+        -> Option<MaterialEffect>;
+
+    fn emitted(&self, _collision: &Collision) -> Color {
+        Color::default()
+    }
+
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // The BSDF times the cosine term for light leaving along `direction` (a unit vector).
+    // Only non-specular materials, the ones light sampling can help, need to implement this.
+    fn evaluate(&self, _ray: Ray, _collision: &Collision, _direction: Vector) -> Color {
+        Color::default()
+    }
+
+    // The solid angle density with which `scatter` picks `direction` (a unit vector).
+    fn pdf(&self, _ray: Ray, _collision: &Collision, _direction: Vector) -> f64 {
+        0.0
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
 pub struct MaterialEffect {
     pub scatter: Ray,
-    pub attenuation: Vector,
+    pub attenuation: Color,
+    // The density `scatter` was sampled with, or `None` for perfectly specular (delta)
+    // scattering which light sampling can never produce.
+    pub pdf: Option<f64>,
+}
+
+// Flips `normal` onto the side of the surface `ray` arrived from.
+fn facing(ray: Ray, normal: Vector) -> Vector {
+    if ray.direction.dot(normal) > 0.0 {
+        -normal
+    } else {
+        normal
+    }
 }
 
 fn reflect(light: Vector, normal: Vector) -> Vector {
@@ -40,40 +71,73 @@ fn schlick(cosine: f64, refraction_index: f64) -> f64 {
     r1 + (1.0 - r1) * (1.0 - cosine).powi(5)
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Vector,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(albedo: Vector) -> Lambertian {
+        Lambertian::textured(Arc::new(SolidColor::new(Color::from_vector(albedo))))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Lambertian {
         Lambertian { albedo }
     }
 }
 
+fn albedo_at(texture: &dyn Texture, collision: &Collision) -> Color {
+    texture.value(collision.u, collision.v, collision.point)
+}
+
 impl Material for Lambertian {
     fn scatter(
-        self: &Self,
-        _ray: Ray,
+        &self,
+        ray: Ray,
         collision: Collision,
         rng: &mut SmallRng,
     ) -> Option<MaterialEffect> {
-        let target = collision.point + collision.normal + random_in_unit_sphere(rng);
+        let normal = facing(ray, collision.normal);
+        let mut direction = normal + random_unit_vector(rng);
+        if direction.squared_length() < 1e-12 {
+            direction = normal;
+        }
         Some(MaterialEffect {
-            scatter: Ray::new(collision.point, target - collision.point),
-            attenuation: self.albedo,
+            scatter: Ray::new(collision.point, direction, ray.time),
+            attenuation: albedo_at(&*self.albedo, &collision),
+            pdf: Some(self.pdf(ray, &collision, direction.to_unit())),
         })
     }
+
+    fn evaluate(&self, ray: Ray, collision: &Collision, direction: Vector) -> Color {
+        let cosine = facing(ray, collision.normal).dot(direction);
+        if cosine <= 0.0 {
+            return Color::default();
+        }
+        albedo_at(&*self.albedo, collision) * (cosine / f64::consts::PI)
+    }
+
+    fn pdf(&self, ray: Ray, collision: &Collision, direction: Vector) -> f64 {
+        let cosine = facing(ray, collision.normal).dot(direction);
+        if cosine <= 0.0 {
+            return 0.0;
+        }
+        cosine / f64::consts::PI
+    }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone)]
 pub struct Metal {
-    albedo: Vector,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Vector, fuzz: f64) -> Metal {
+        Metal::textured(Arc::new(SolidColor::new(Color::from_vector(albedo))), fuzz)
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>, fuzz: f64) -> Metal {
         let fuzz = if fuzz > 1.0 { 1.0 } else { fuzz };
         Metal { albedo, fuzz }
     }
@@ -81,7 +145,7 @@ impl Metal {
 
 impl Material for Metal {
     fn scatter(
-        self: &Self,
+        &self,
         ray: Ray,
         collision: Collision,
         rng: &mut SmallRng,
@@ -90,11 +154,13 @@ impl Material for Metal {
         let scatter = Ray::new(
             collision.point,
             reflected + random_in_unit_sphere(rng) * self.fuzz,
+            ray.time,
         );
         if scatter.direction.dot(collision.normal) > 0.0 {
             Some(MaterialEffect {
                 scatter,
-                attenuation: self.albedo,
+                attenuation: albedo_at(&*self.albedo, &collision),
+                pdf: None,
             })
         } else {
             None
@@ -115,7 +181,7 @@ impl Dialectic {
 
 impl Material for Dialectic {
     fn scatter(
-        self: &Self,
+        &self,
         ray: Ray,
         collision: Collision,
         rng: &mut SmallRng,
@@ -127,14 +193,14 @@ impl Material for Dialectic {
         let mut scatter_direction;
 
         if ray.direction.dot(collision.normal) > 0.0 {
-            outward_normal = collision.normal * -1.0;
+            outward_normal = -collision.normal;
             refraction_index = self.refraction_index;
             cosine =
                 refraction_index * ray.direction.dot(collision.normal) / ray.direction.length();
         } else {
             outward_normal = collision.normal;
             refraction_index = 1.0 / self.refraction_index;
-            cosine = ray.direction.dot(collision.normal) / ray.direction.length() * -1.0;
+            cosine = -ray.direction.dot(collision.normal) / ray.direction.length();
         }
 
         if let Some(refraction) = refract(ray.direction, outward_normal, refraction_index) {
@@ -149,8 +215,80 @@ impl Material for Dialectic {
             scatter_direction = reflect(ray.direction, collision.normal);
         }
         Some(MaterialEffect {
-            scatter: Ray::new(collision.point, scatter_direction),
-            attenuation: Vector::unit(),
+            scatter: Ray::new(collision.point, scatter_direction, ray.time),
+            attenuation: Color::new(1.0, 1.0, 1.0),
+            pdf: None,
         })
     }
 }
+
+// The phase function of a participating medium that scatters light equally in every
+// direction. Volumes have no surface, so the collision normal is ignored.
+#[derive(Clone)]
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Isotropic {
+        Isotropic::textured(Arc::new(SolidColor::new(albedo)))
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(
+        &self,
+        ray: Ray,
+        collision: Collision,
+        rng: &mut SmallRng,
+    ) -> Option<MaterialEffect> {
+        let direction = random_unit_vector(rng);
+        Some(MaterialEffect {
+            scatter: Ray::new(collision.point, direction, ray.time),
+            attenuation: albedo_at(&*self.albedo, &collision),
+            pdf: Some(self.pdf(ray, &collision, direction)),
+        })
+    }
+
+    fn evaluate(&self, _ray: Ray, collision: &Collision, _direction: Vector) -> Color {
+        albedo_at(&*self.albedo, collision) * (1.0 / (4.0 * f64::consts::PI))
+    }
+
+    fn pdf(&self, _ray: Ray, _collision: &Collision, _direction: Vector) -> f64 {
+        1.0 / (4.0 * f64::consts::PI)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> DiffuseLight {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray: Ray,
+        _collision: Collision,
+        _rng: &mut SmallRng,
+    ) -> Option<MaterialEffect> {
+        None
+    }
+
+    fn emitted(&self, _collision: &Collision) -> Color {
+        self.emit
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+}
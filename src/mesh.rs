@@ -0,0 +1,263 @@
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::collision::{area_to_solid_angle, Collidable, Collision, SurfaceSample};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::shape::{
+    intersect_triangle, sample_triangle, triangle_area, triangle_collision, triangle_normal,
+};
+use crate::vector::Vector;
+
+// How far either side of a point, as a fraction of the distance to it, to look for the face
+// it lies on.
+const FACE_SEARCH: f64 = 1e-6;
+
+struct MeshData {
+    positions: Vec<Vector>,
+    normals: Vec<Vector>,
+    uvs: Vec<(f64, f64)>,
+    faces: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl MeshData {
+    fn vertices(&self, face: usize) -> [Vector; 3] {
+        let [a, b, c] = self.faces[face];
+        [self.positions[a], self.positions[b], self.positions[c]]
+    }
+
+    fn normals(&self, face: usize) -> Option<[Vector; 3]> {
+        if self.normals.is_empty() {
+            return None;
+        }
+        let [a, b, c] = self.faces[face];
+        Some([self.normals[a], self.normals[b], self.normals[c]])
+    }
+
+    fn uvs(&self, face: usize) -> Option<[(f64, f64); 3]> {
+        if self.uvs.is_empty() {
+            return None;
+        }
+        let [a, b, c] = self.faces[face];
+        Some([self.uvs[a], self.uvs[b], self.uvs[c]])
+    }
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: usize,
+}
+
+impl Collidable for MeshTriangle {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let vertices = self.mesh.vertices(self.face);
+        let (time, weights) = intersect_triangle(ray, &vertices, tmin, tmax)?;
+        Some(triangle_collision(
+            ray,
+            time,
+            weights,
+            &vertices,
+            self.mesh.normals(self.face).as_ref(),
+            self.mesh.uvs(self.face).as_ref(),
+            &*self.mesh.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&self.mesh.vertices(self.face))
+    }
+}
+
+// A triangle mesh whose faces index into vertex buffers shared by every triangle. `normals`
+// and `uvs` are either empty or have one entry per position.
+pub struct Mesh {
+    data: Arc<MeshData>,
+    triangles: Bvh<MeshTriangle>,
+    // Running totals of face areas, only kept for emissive meshes so lights can be sampled
+    // uniformly over their surface.
+    area_cdf: Vec<f64>,
+}
+
+impl Mesh {
+    pub fn new(
+        positions: Vec<Vector>,
+        normals: Vec<Vector>,
+        uvs: Vec<(f64, f64)>,
+        faces: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Mesh {
+        assert!(
+            normals.is_empty() || normals.len() == positions.len(),
+            "mesh has {} normals for {} positions",
+            normals.len(),
+            positions.len()
+        );
+        assert!(
+            uvs.is_empty() || uvs.len() == positions.len(),
+            "mesh has {} uvs for {} positions",
+            uvs.len(),
+            positions.len()
+        );
+        assert!(
+            faces.iter().flatten().all(|&index| index < positions.len()),
+            "mesh face indexes past its {} positions",
+            positions.len()
+        );
+
+        let face_count = faces.len();
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            faces,
+            material,
+        });
+        let triangles = (0..face_count)
+            .map(|face| MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                face,
+            })
+            .collect();
+
+        let area_cdf = if mesh.material.is_emissive() {
+            (0..face_count)
+                .scan(0.0, |total, face| {
+                    *total += triangle_area(&mesh.vertices(face));
+                    Some(*total)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Mesh {
+            data: mesh,
+            triangles: Bvh::new(triangles),
+            area_cdf,
+        }
+    }
+
+    fn area(&self) -> f64 {
+        self.area_cdf.last().cloned().unwrap_or(0.0)
+    }
+
+    // The face `point` lies on, found again by looking from `origin` since collisions do not
+    // say which face they hit.
+    fn face_at(&self, origin: Vector, point: Vector) -> Option<usize> {
+        let ray = Ray::new(origin, point - origin, 0.0);
+        let (tmin, tmax) = (1.0 - FACE_SEARCH, 1.0 + FACE_SEARCH);
+        let mut face = None;
+        self.triangles.closest(ray, tmin, tmax, |triangle, tmax| {
+            let collision = triangle.check_collision(ray, tmin, tmax)?;
+            face = Some(triangle.face);
+            Some(collision)
+        });
+        face
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+}
+
+impl Collidable for Mesh {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        self.triangles.check_collision(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.triangles.bounding_box()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.data.material.is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        let area = self.area();
+        if area == 0.0 {
+            return None;
+        }
+        let target = rng.gen::<f64>() * area;
+        let face = self
+            .area_cdf
+            .partition_point(|&total| total <= target)
+            .min(self.area_cdf.len() - 1);
+        let (point, normal) = sample_triangle(&self.data.vertices(face), rng);
+        Some(SurfaceSample {
+            point,
+            normal,
+            pdf: area_to_solid_angle(1.0 / area, origin, point, normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        let area = self.area();
+        if area == 0.0 {
+            return 0.0;
+        }
+        // Light samples are drawn with the flat face normal, so the density of a hit has to
+        // be measured with it too rather than with the interpolated shading normal.
+        let normal = match self.face_at(origin, collision.point) {
+            Some(face) => triangle_normal(&self.data.vertices(face)),
+            None => collision.normal,
+        };
+        area_to_solid_angle(1.0 / area, origin, collision.point, normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::DiffuseLight;
+
+    // A unit square light at y = 1 facing down, with vertex normals tilted well away from
+    // the faces' own.
+    fn smooth_light() -> Mesh {
+        Mesh::new(
+            vec![
+                Vector::new(0.0, 1.0, 0.0),
+                Vector::new(0.0, 1.0, 1.0),
+                Vector::new(1.0, 1.0, 1.0),
+                Vector::new(1.0, 1.0, 0.0),
+            ],
+            vec![
+                Vector::new(0.6, -0.8, 0.0),
+                Vector::new(-0.6, -0.8, 0.0),
+                Vector::new(0.0, -0.8, 0.6),
+                Vector::new(0.0, -0.8, -0.6),
+            ],
+            Vec::new(),
+            vec![[0, 1, 2], [0, 2, 3]],
+            Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0))),
+        )
+    }
+
+    #[test]
+    fn surface_pdf_matches_light_samples() {
+        let mesh = smooth_light();
+        let origin = Vector::new(0.2, 0.0, 0.3);
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let sample = mesh.sample_surface(origin, &mut rng).unwrap();
+            let ray = Ray::new(origin, sample.point - origin, 0.0);
+            let collision = mesh.check_collision(ray, 0.001, f64::MAX).unwrap();
+            let pdf = mesh.surface_pdf(origin, &collision);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-6 * sample.pdf,
+                "{} vs {}",
+                pdf,
+                sample.pdf
+            );
+        }
+    }
+}
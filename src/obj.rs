@@ -0,0 +1,654 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::{FromStr, SplitWhitespace};
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::material::{Dialectic, DiffuseLight, Lambertian, Material, Metal};
+use crate::mesh::Mesh;
+use crate::texture::{ImageTexture, SolidColor, Texture};
+use crate::vector::Vector;
+
+pub struct ObjGroup {
+    pub name: String,
+    pub material: Option<String>,
+    pub mesh: Mesh,
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(PathBuf, io::Error),
+    Invalid {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            ObjError::Invalid {
+                path,
+                line,
+                message,
+            } => write!(f, "{}, line {}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+#[derive(Clone, Default)]
+struct MtlDescription {
+    diffuse: Option<Vector>,
+    diffuse_map: Option<Arc<dyn Texture>>,
+    specular: Option<Vector>,
+    emission: Option<Vector>,
+    shininess: Option<f64>,
+    refraction_index: Option<f64>,
+    dissolve: Option<f64>,
+    illumination: Option<u32>,
+}
+
+impl MtlDescription {
+    fn to_material(&self) -> Arc<dyn Material> {
+        let transparent = self.dissolve.is_some_and(|dissolve| dissolve < 1.0)
+            || matches!(self.illumination, Some(4) | Some(6) | Some(7) | Some(9));
+        let reflective = matches!(self.illumination, Some(3) | Some(5) | Some(8));
+
+        if let Some(emission) = self
+            .emission
+            .filter(|emission| emission.squared_length() > 0.0)
+        {
+            Arc::new(DiffuseLight::new(Color::from_vector(emission)))
+        } else if transparent {
+            Arc::new(Dialectic::new(self.refraction_index.unwrap_or(1.5)))
+        } else if reflective {
+            // Map the Phong exponent onto a roughness in [0, 1]: Ns = 0 is fully rough and
+            // large exponents approach a perfect mirror.
+            let fuzz = (2.0 / (self.shininess.unwrap_or(0.0).max(0.0) + 2.0)).sqrt();
+            let albedo = self
+                .specular
+                .or(self.diffuse)
+                .unwrap_or_else(|| Vector::unit() * 0.8);
+            Arc::new(Metal::new(albedo, fuzz))
+        } else {
+            let albedo = self.diffuse_map.clone().unwrap_or_else(|| {
+                let diffuse = self.diffuse.unwrap_or_else(|| Vector::unit() * 0.8);
+                Arc::new(SolidColor::new(Color::from_vector(diffuse)))
+            });
+            Arc::new(Lambertian::textured(albedo))
+        }
+    }
+}
+
+struct LineParser<'a> {
+    path: &'a Path,
+    line: usize,
+}
+
+impl<'a> LineParser<'a> {
+    fn error<M: Into<String>>(&self, message: M) -> ObjError {
+        ObjError::Invalid {
+            path: self.path.to_path_buf(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn number<T: FromStr>(&self, tokens: &mut SplitWhitespace, what: &str) -> Result<T, ObjError> {
+        let token = tokens
+            .next()
+            .ok_or_else(|| self.error(format!("missing {}", what)))?;
+        token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} `{}`", what, token)))
+    }
+
+    fn vector(&self, tokens: &mut SplitWhitespace) -> Result<Vector, ObjError> {
+        Ok(Vector::new(
+            self.number(tokens, "x coordinate")?,
+            self.number(tokens, "y coordinate")?,
+            self.number(tokens, "z coordinate")?,
+        ))
+    }
+
+    fn index(&self, token: &str, count: usize, what: &str) -> Result<usize, ObjError> {
+        let index: isize = token
+            .parse()
+            .map_err(|_| self.error(format!("invalid {} index `{}`", what, token)))?;
+        let resolved = if index < 0 {
+            count as isize + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved >= count as isize {
+            return Err(self.error(format!(
+                "{} index {} is out of range, {} defined so far",
+                what, index, count
+            )));
+        }
+        Ok(resolved as usize)
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|error| ObjError::Io(path.to_path_buf(), error))
+}
+
+// The file name of a texture map statement. Options such as `-s 2 2 1` or `-clamp on` come
+// first and are skipped, since only the image is used; the rest of the line is the name.
+fn map_file(tokens: SplitWhitespace) -> Option<String> {
+    let mut tokens = tokens.peekable();
+    while let Some(option) = tokens.next_if(|token| token.starts_with('-')) {
+        match option {
+            "-blendu" | "-blendv" | "-cc" | "-clamp" | "-imfchan" | "-type" => {
+                tokens.next();
+            }
+            // The rest take up to three numbers.
+            _ => {
+                for _ in 0..3 {
+                    if tokens
+                        .next_if(|token| token.parse::<f64>().is_ok())
+                        .is_none()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    let file = tokens.collect::<Vec<_>>().join(" ");
+    if file.is_empty() {
+        None
+    } else {
+        Some(file)
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, MtlDescription>, ObjError> {
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let source = read(path)?;
+    let mut images: HashMap<String, Arc<dyn Texture>> = HashMap::new();
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlDescription)> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let parser = LineParser {
+            path,
+            line: index + 1,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            if name.is_empty() {
+                return Err(parser.error("newmtl without a name"));
+            }
+            if let Some((name, material)) = current.take() {
+                materials.insert(name, material);
+            }
+            current = Some((name, MtlDescription::default()));
+            continue;
+        }
+
+        let material = match current.as_mut() {
+            Some((_, material)) => material,
+            None => return Err(parser.error(format!("`{}` before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => material.diffuse = Some(parser.vector(&mut tokens)?),
+            "Ks" => material.specular = Some(parser.vector(&mut tokens)?),
+            "Ke" => material.emission = Some(parser.vector(&mut tokens)?),
+            "Ns" => material.shininess = Some(parser.number(&mut tokens, "shininess")?),
+            "Ni" => {
+                material.refraction_index = Some(parser.number(&mut tokens, "refraction index")?)
+            }
+            "d" => material.dissolve = Some(parser.number(&mut tokens, "dissolve")?),
+            "Tr" => {
+                let transparency: f64 = parser.number(&mut tokens, "transparency")?;
+                material.dissolve = Some(1.0 - transparency);
+            }
+            "illum" => {
+                material.illumination = Some(parser.number(&mut tokens, "illumination model")?)
+            }
+            "map_Kd" => {
+                let file =
+                    map_file(tokens).ok_or_else(|| parser.error("map_Kd without a file name"))?;
+                let texture = match images.get(&file) {
+                    Some(texture) => Arc::clone(texture),
+                    None => {
+                        let image = ImageTexture::load(directory.join(&file))
+                            .map_err(|error| parser.error(format!("{}: {}", file, error)))?;
+                        let texture: Arc<dyn Texture> = Arc::new(image);
+                        images.insert(file, Arc::clone(&texture));
+                        texture
+                    }
+                };
+                material.diffuse_map = Some(texture);
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, material)) = current {
+        materials.insert(name, material);
+    }
+    Ok(materials)
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct FaceVertex {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+struct GroupBuilder {
+    name: String,
+    material: Option<String>,
+    faces: Vec<[FaceVertex; 3]>,
+}
+
+impl GroupBuilder {
+    fn build(
+        self,
+        positions: &[Vector],
+        uvs: &[(f64, f64)],
+        normals: &[Vector],
+        material: Arc<dyn Material>,
+    ) -> ObjGroup {
+        // Only keep normals and uvs when every corner in the group has one; a partial set
+        // cannot be interpolated consistently.
+        let flat = self.faces.iter().flatten();
+        let with_uvs = flat.clone().all(|vertex| vertex.uv.is_some());
+        let with_normals = flat.clone().all(|vertex| vertex.normal.is_some());
+
+        let mut vertices: HashMap<FaceVertex, usize> = HashMap::new();
+        let mut mesh_positions = Vec::new();
+        let mut mesh_uvs = Vec::new();
+        let mut mesh_normals = Vec::new();
+        let mut faces = Vec::with_capacity(self.faces.len());
+
+        for face in &self.faces {
+            let mut indices = [0; 3];
+            for (corner, vertex) in face.iter().enumerate() {
+                let key = FaceVertex {
+                    position: vertex.position,
+                    uv: vertex.uv.filter(|_| with_uvs),
+                    normal: vertex.normal.filter(|_| with_normals),
+                };
+                indices[corner] = *vertices.entry(key).or_insert_with(|| {
+                    mesh_positions.push(positions[key.position]);
+                    if let Some(uv) = key.uv {
+                        mesh_uvs.push(uvs[uv]);
+                    }
+                    if let Some(normal) = key.normal {
+                        mesh_normals.push(normals[normal].to_unit());
+                    }
+                    mesh_positions.len() - 1
+                });
+            }
+            faces.push(indices);
+        }
+
+        ObjGroup {
+            name: self.name,
+            material: self.material,
+            mesh: Mesh::new(mesh_positions, mesh_normals, mesh_uvs, faces, material),
+        }
+    }
+}
+
+// Loads the polygons of a Wavefront OBJ file as one mesh per group and material. Materials
+// come from the file's `mtllib` libraries; faces without one use `default_material`.
+pub fn load_obj<P: AsRef<Path>>(
+    path: P,
+    default_material: Arc<dyn Material>,
+) -> Result<Vec<ObjGroup>, ObjError> {
+    let path = path.as_ref();
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    let source = read(path)?;
+
+    let mut positions: Vec<Vector> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut normals: Vec<Vector> = Vec::new();
+    let mut library: HashMap<String, MtlDescription> = HashMap::new();
+
+    let mut groups: Vec<GroupBuilder> = Vec::new();
+    let mut group_name = String::from("default");
+    let mut material_name: Option<String> = None;
+
+    for (index, line) in source.lines().enumerate() {
+        let parser = LineParser {
+            path,
+            line: index + 1,
+        };
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) if !keyword.starts_with('#') => keyword,
+            _ => continue,
+        };
+
+        match keyword {
+            "v" => positions.push(parser.vector(&mut tokens)?),
+            "vt" => {
+                let u = parser.number(&mut tokens, "u coordinate")?;
+                let v = tokens.next().map_or(Ok(0.0), |token| {
+                    token
+                        .parse()
+                        .map_err(|_| parser.error(format!("invalid v coordinate `{}`", token)))
+                })?;
+                uvs.push((u, v));
+            }
+            "vn" => normals.push(parser.vector(&mut tokens)?),
+            "g" | "o" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                group_name = if name.is_empty() {
+                    String::from("default")
+                } else {
+                    name
+                };
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                if !library.contains_key(&name) {
+                    return Err(parser.error(format!("unknown material `{}`", name)));
+                }
+                material_name = Some(name);
+            }
+            "mtllib" => {
+                for file in tokens {
+                    library.extend(load_mtl(&directory.join(file))?);
+                }
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|token| {
+                        let mut parts = token.split('/');
+                        let position =
+                            parser.index(parts.next().unwrap_or(""), positions.len(), "vertex")?;
+                        let uv = match parts.next() {
+                            Some(part) if !part.is_empty() => {
+                                Some(parser.index(part, uvs.len(), "texture coordinate")?)
+                            }
+                            _ => None,
+                        };
+                        let normal = match parts.next() {
+                            Some(part) if !part.is_empty() => {
+                                Some(parser.index(part, normals.len(), "normal")?)
+                            }
+                            _ => None,
+                        };
+                        Ok(FaceVertex {
+                            position,
+                            uv,
+                            normal,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ObjError>>()?;
+                if corners.len() < 3 {
+                    return Err(parser.error(format!(
+                        "face needs at least 3 vertices, found {}",
+                        corners.len()
+                    )));
+                }
+
+                let current = match groups.last_mut() {
+                    Some(group) if group.name == group_name && group.material == material_name => {
+                        group
+                    }
+                    _ => {
+                        groups.push(GroupBuilder {
+                            name: group_name.clone(),
+                            material: material_name.clone(),
+                            faces: Vec::new(),
+                        });
+                        groups.last_mut().unwrap()
+                    }
+                };
+                // Polygons are assumed convex and split into a triangle fan.
+                for i in 1..corners.len() - 1 {
+                    current.faces.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    Ok(groups
+        .into_iter()
+        .map(|group| {
+            let material = match &group.material {
+                Some(name) => Arc::clone(
+                    materials
+                        .entry(name.clone())
+                        .or_insert_with(|| library[name].to_material()),
+                ),
+                None => Arc::clone(&default_material),
+            };
+            group.build(&positions, &uvs, &normals, material)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collision::Collidable;
+    use crate::ray::Ray;
+    use image::{ImageBuffer, Rgb, RgbImage};
+    use std::f64;
+    use std::process;
+
+    // Writes `files` to a fresh directory and returns it.
+    fn directory(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("man-ray-obj-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (file, contents) in files {
+            fs::write(directory.join(file), contents).unwrap();
+        }
+        directory
+    }
+
+    fn load(name: &str, files: &[(&str, &str)]) -> Result<Vec<ObjGroup>, ObjError> {
+        let directory = directory(name, files);
+        let material: Arc<dyn Material> = Arc::new(Lambertian::new(Vector::unit() * 0.5));
+        let groups = load_obj(directory.join("model.obj"), material);
+        fs::remove_dir_all(&directory).unwrap();
+        groups
+    }
+
+    // Looks straight down onto the z = 0 plane at (`x`, `y`).
+    fn hit(mesh: &Mesh, x: f64, y: f64) -> Option<(f64, f64)> {
+        let ray = Ray::new(Vector::new(x, y, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        mesh.check_collision(ray, 0.001, f64::MAX)
+            .map(|collision| (collision.u, collision.v))
+    }
+
+    fn assert_uv(found: Option<(f64, f64)>, expected: (f64, f64)) {
+        let (u, v) = found.expect("no hit");
+        assert!(
+            (u - expected.0).abs() < 1e-9 && (v - expected.1).abs() < 1e-9,
+            "{:?} vs {:?}",
+            (u, v),
+            expected
+        );
+    }
+
+    #[test]
+    fn resolves_relative_indices_and_index_forms() {
+        // Each triangle's uvs match its x and y, so a hit shows which were picked up.
+        let source = "v 9 9 9
+vt 0.9 0.9
+vn 1 0 0
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0 1
+vn 0 0 1
+vn 0 0 1
+vn 0 0 1
+g relative
+f -3/-3/-3 -2/-2/-2 -1/-1/-1
+g absolute
+f 2/2/2 3/3/3 4/4/4
+g uvs
+f 2/2 3/3 4/4
+g normals
+f 2//2 3//3 4//4
+g positions
+f 2 3 4
+";
+        let groups = load("indices", &[("model.obj", source)]).unwrap();
+        let names: Vec<&str> = groups.iter().map(|group| group.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["relative", "absolute", "uvs", "normals", "positions"]
+        );
+        for group in &groups[..3] {
+            assert_uv(hit(&group.mesh, 0.25, 0.5), (0.25, 0.5));
+        }
+        for group in &groups[3..] {
+            assert!(hit(&group.mesh, 0.25, 0.5).is_some());
+            assert!(hit(&group.mesh, 0.75, 0.75).is_none());
+        }
+    }
+
+    #[test]
+    fn reports_out_of_range_indices() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n";
+        match load("range", &[("model.obj", source)]) {
+            Err(ObjError::Invalid { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(message, "vertex index -4 is out of range, 3 defined so far");
+            }
+            _ => panic!("expected an invalid index"),
+        }
+    }
+
+    #[test]
+    fn triangulates_polygons_as_fans() {
+        let source = "v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 2 0 0
+v 3 0 0
+v 3 1 0
+v 2.5 1.5 0
+v 2 1 0
+f 1 2 3 4
+f 5 6 7 8 9
+";
+        let groups = load("fans", &[("model.obj", source)]).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].mesh.len(), 2 + 3);
+        // Every part of both polygons is covered, and nothing between them.
+        for &(x, y) in &[(0.1, 0.9), (0.9, 0.1), (2.1, 0.9), (2.9, 0.9), (2.5, 1.4)] {
+            assert!(hit(&groups[0].mesh, x, y).is_some(), "{}, {}", x, y);
+        }
+        assert!(hit(&groups[0].mesh, 1.5, 0.5).is_none());
+    }
+
+    #[test]
+    fn splits_by_group_and_material() {
+        let library = "newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n";
+        let source = "mtllib materials.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+g first
+usemtl red
+f 1 2 3
+f 1 2 3
+usemtl blue
+f 1 2 3
+g second
+f 1 2 3
+usemtl red
+f 1 2 3
+";
+        let groups = load(
+            "groups",
+            &[("model.obj", source), ("materials.mtl", library)],
+        )
+        .unwrap();
+        let split: Vec<(&str, Option<&str>, usize)> = groups
+            .iter()
+            .map(|group| {
+                (
+                    group.name.as_str(),
+                    group.material.as_deref(),
+                    group.mesh.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            split,
+            [
+                ("default", None, 1),
+                ("first", Some("red"), 2),
+                ("first", Some("blue"), 1),
+                ("second", Some("blue"), 1),
+                ("second", Some("red"), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_materials() {
+        let source = "mtllib materials.mtl\nv 0 0 0\n\nusemtl green\n";
+        match load(
+            "unknown",
+            &[("model.obj", source), ("materials.mtl", "newmtl red\n")],
+        ) {
+            Err(ObjError::Invalid { line, message, .. }) => {
+                assert_eq!(line, 4);
+                assert_eq!(message, "unknown material `green`");
+            }
+            _ => panic!("expected an unknown material"),
+        }
+    }
+
+    #[test]
+    fn skips_texture_map_options() {
+        let image: RgbImage = ImageBuffer::from_pixel(2, 2, Rgb { data: [255, 0, 0] });
+        let directory = directory("maps", &[]);
+        image.save(directory.join("red brick.png")).unwrap();
+        image.save(directory.join("2.png")).unwrap();
+        let library = "newmtl brick
+map_Kd -s 2 2 1 -clamp on -bm 0.5 red brick.png
+newmtl numbered
+map_Kd -o 0.5 2.png
+";
+        fs::write(directory.join("materials.mtl"), library).unwrap();
+        let materials = load_mtl(&directory.join("materials.mtl"));
+        fs::remove_dir_all(&directory).unwrap();
+
+        let materials = materials.unwrap();
+        assert!(materials["brick"].diffuse_map.is_some());
+        assert!(materials["numbered"].diffuse_map.is_some());
+
+        assert_eq!(
+            map_file("-blendu off -mm 0 1 texture.png".split_whitespace()),
+            Some(String::from("texture.png"))
+        );
+        assert_eq!(map_file("-s 1 1 1".split_whitespace()), None);
+    }
+}
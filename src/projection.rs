@@ -0,0 +1,234 @@
+use std::f64;
+
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// How a camera maps its frame onto rays. Rays are given in the camera's own space, where x is
+// right, y is up and the camera looks down -z, for (`x`, `y`) running from -1 to 1 across the
+// frame from its bottom left corner. Parts of the frame that see nothing give `None`.
+//
+// Lens focus is applied along the returned direction: whatever lies at the focus distance
+// times its length is sharp.
+pub trait Projection: Send + Sync {
+    fn ray(&self, x: f64, y: f64, aspect: f64) -> Option<Ray>;
+
+    // Which way, in camera space, the right eye of a stereo pair sits from the center when
+    // looking along `direction`. Its length scales the eyes' separation.
+    fn eye_offset(&self, _direction: Vector) -> Vector {
+        Vector::new(1.0, 0.0, 0.0)
+    }
+}
+
+// A pinhole perspective with a vertical field of view in degrees. Directions are one unit
+// deep, so the plane of focus faces the camera.
+#[derive(Clone, Copy, Debug)]
+pub struct Perspective {
+    half_height: f64,
+}
+
+impl Perspective {
+    pub fn new(field_of_view: f64) -> Perspective {
+        Perspective {
+            half_height: (field_of_view * f64::consts::PI / 360.0).tan(),
+        }
+    }
+}
+
+impl Projection for Perspective {
+    fn ray(&self, x: f64, y: f64, aspect: f64) -> Option<Ray> {
+        let direction = Vector::new(x * self.half_height * aspect, y * self.half_height, -1.0);
+        Some(Ray::new(Vector::default(), direction, 0.0))
+    }
+}
+
+// Parallel rays from a frame `height` units tall, so sizes do not change with distance.
+#[derive(Clone, Copy, Debug)]
+pub struct Orthographic {
+    height: f64,
+}
+
+impl Orthographic {
+    pub fn new(height: f64) -> Orthographic {
+        Orthographic { height }
+    }
+}
+
+impl Projection for Orthographic {
+    fn ray(&self, x: f64, y: f64, aspect: f64) -> Option<Ray> {
+        let half_height = self.height / 2.0;
+        let origin = Vector::new(x * half_height * aspect, y * half_height, 0.0);
+        Some(Ray::new(origin, Vector::new(0.0, 0.0, -1.0), 0.0))
+    }
+}
+
+// An equidistant fisheye: the angle from the view axis grows in step with distance from the
+// frame's center. The image circle fills the frame's height and spans `field_of_view` degrees,
+// which may go past 180.
+#[derive(Clone, Copy, Debug)]
+pub struct Fisheye {
+    half_angle: f64,
+}
+
+impl Fisheye {
+    pub fn new(field_of_view: f64) -> Fisheye {
+        Fisheye {
+            half_angle: field_of_view * f64::consts::PI / 360.0,
+        }
+    }
+}
+
+impl Projection for Fisheye {
+    fn ray(&self, x: f64, y: f64, aspect: f64) -> Option<Ray> {
+        let x = x * aspect;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+        let theta = radius * self.half_angle;
+        let phi = y.atan2(x);
+        let direction = Vector::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            -theta.cos(),
+        );
+        Some(Ray::new(Vector::default(), direction, 0.0))
+    }
+}
+
+// A full 360° by 180° latitude-longitude panorama, laid out like the environment maps it can
+// be used as: the center of the frame looks ahead and its edges directly behind. Frames with
+// an aspect ratio of 2 keep pixels square.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Equirectangular;
+
+impl Projection for Equirectangular {
+    fn ray(&self, x: f64, y: f64, _aspect: f64) -> Option<Ray> {
+        Some(Ray::new(Vector::default(), panorama_direction(x, y), 0.0))
+    }
+    // Omnidirectional stereo: the eyes turn with the view so every direction has depth. They
+    // draw together towards the poles, where turning around would otherwise swap them.
+    fn eye_offset(&self, direction: Vector) -> Vector {
+        Vector::new(-direction.z, 0.0, direction.x)
+    }
+}
+
+// The unit direction at (`x`, `y`) in a latitude-longitude panorama.
+fn panorama_direction(x: f64, y: f64) -> Vector {
+    let longitude = x * f64::consts::PI;
+    let latitude = y * f64::consts::FRAC_PI_2;
+    Vector::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(found: Vector, expected: Vector) {
+        assert!(
+            (found - expected).length() < 1e-9,
+            "{:?} vs {:?}",
+            found,
+            expected
+        );
+    }
+
+    fn direction(projection: &dyn Projection, x: f64, y: f64, aspect: f64) -> Vector {
+        projection.ray(x, y, aspect).unwrap().direction.to_unit()
+    }
+
+    #[test]
+    fn perspective_rays() {
+        // A 90° field of view puts the top of the frame one unit up at one unit deep.
+        let perspective = Perspective::new(90.0);
+        let center = perspective.ray(0.0, 0.0, 2.0).unwrap();
+        assert_close(center.origin, Vector::default());
+        assert_close(center.direction, Vector::new(0.0, 0.0, -1.0));
+        assert_close(
+            perspective.ray(1.0, 1.0, 2.0).unwrap().direction,
+            Vector::new(2.0, 1.0, -1.0),
+        );
+        assert_close(
+            perspective.ray(-1.0, -1.0, 2.0).unwrap().direction,
+            Vector::new(-2.0, -1.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn orthographic_rays() {
+        let orthographic = Orthographic::new(4.0);
+        for &(x, y) in &[(0.0, 0.0), (1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)] {
+            let ray = orthographic.ray(x, y, 1.5).unwrap();
+            assert_close(ray.origin, Vector::new(x * 3.0, y * 2.0, 0.0));
+            assert_close(ray.direction, Vector::new(0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn fisheye_rays() {
+        let fisheye = Fisheye::new(180.0);
+        assert_close(
+            direction(&fisheye, 0.0, 0.0, 1.0),
+            Vector::new(0.0, 0.0, -1.0),
+        );
+        // The edge of the image circle looks 90° off axis.
+        assert_close(
+            direction(&fisheye, 0.0, 1.0, 1.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        assert_close(
+            direction(&fisheye, 0.5, 0.0, 2.0),
+            Vector::new(1.0, 0.0, 0.0),
+        );
+        // Corners fall outside the image circle.
+        assert!(fisheye.ray(1.0, 1.0, 1.0).is_none());
+        assert!(fisheye.ray(-1.0, -1.0, 2.0).is_none());
+    }
+
+    #[test]
+    fn equirectangular_rays() {
+        let equirectangular = Equirectangular;
+        assert_close(
+            direction(&equirectangular, 0.0, 0.0, 2.0),
+            Vector::new(0.0, 0.0, -1.0),
+        );
+        assert_close(
+            direction(&equirectangular, 0.5, 0.0, 2.0),
+            Vector::new(1.0, 0.0, 0.0),
+        );
+        assert_close(
+            direction(&equirectangular, -1.0, 0.0, 2.0),
+            Vector::new(0.0, 0.0, 1.0),
+        );
+        // Every corner looks straight up or straight down.
+        assert_close(
+            direction(&equirectangular, 1.0, 1.0, 2.0),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        assert_close(
+            direction(&equirectangular, -1.0, -1.0, 2.0),
+            Vector::new(0.0, -1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn eye_offsets() {
+        assert_close(
+            Perspective::new(60.0).eye_offset(Vector::new(0.0, 0.0, 1.0)),
+            Vector::new(1.0, 0.0, 0.0),
+        );
+        // Panorama eyes stay to the right of whichever way the view faces.
+        let equirectangular = Equirectangular;
+        assert_close(
+            equirectangular.eye_offset(Vector::new(0.0, 0.0, -1.0)),
+            Vector::new(1.0, 0.0, 0.0),
+        );
+        assert_close(
+            equirectangular.eye_offset(Vector::new(1.0, 0.0, 0.0)),
+            Vector::new(0.0, 0.0, 1.0),
+        );
+    }
+}
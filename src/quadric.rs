@@ -0,0 +1,601 @@
+use std::f64;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::collision::{Collidable, Collision};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// An orthonormal frame with `z` along a shape's axis. The shapes below are intersected in
+// this frame, where they sit upright at the origin; being orthonormal it leaves ray
+// parameters unchanged.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    origin: Vector,
+    x: Vector,
+    y: Vector,
+    z: Vector,
+}
+
+impl Frame {
+    fn new(origin: Vector, axis: Vector) -> Frame {
+        let z = axis.to_unit();
+        let (x, y) = z.basis();
+        Frame { origin, x, y, z }
+    }
+
+    fn to_local(self, vector: Vector) -> Vector {
+        Vector::new(vector.dot(self.x), vector.dot(self.y), vector.dot(self.z))
+    }
+
+    fn ray(&self, ray: Ray) -> Ray {
+        Ray::new(
+            self.to_local(ray.origin - self.origin),
+            self.to_local(ray.direction),
+            ray.time,
+        )
+    }
+
+    fn to_world(self, vector: Vector) -> Vector {
+        self.x * vector.x + self.y * vector.y + self.z * vector.z
+    }
+
+    // How far a circle of `radius` around the axis reaches along each world axis.
+    fn circle_reach(&self, radius: f64) -> Vector {
+        let z = self.z;
+        Vector::new(
+            (1.0 - z.x * z.x).max(0.0).sqrt(),
+            (1.0 - z.y * z.y).max(0.0).sqrt(),
+            (1.0 - z.z * z.z).max(0.0).sqrt(),
+        ) * radius
+    }
+
+    // Bounds of a circle of `radius` around the axis at `height` along it.
+    fn circle_bounds(&self, height: f64, radius: f64) -> Aabb {
+        let center = self.origin + self.z * height;
+        let reach = self.circle_reach(radius);
+        Aabb::new(center - reach, center + reach)
+    }
+}
+
+// The closest intersection found so far, in the shape's local frame.
+#[derive(Clone, Copy, Debug)]
+struct LocalHit {
+    time: f64,
+    normal: Vector,
+    u: f64,
+    v: f64,
+}
+
+fn closest(hit: Option<LocalHit>, candidate: Option<LocalHit>) -> Option<LocalHit> {
+    match (hit, candidate) {
+        (Some(hit), Some(candidate)) if candidate.time < hit.time => Some(candidate),
+        (None, candidate) => candidate,
+        (hit, _) => hit,
+    }
+}
+
+fn collision<'a>(
+    frame: &Frame,
+    ray: Ray,
+    hit: LocalHit,
+    material: &'a dyn Material,
+) -> Collision<'a> {
+    Collision::new(
+        hit.time,
+        ray.point_at_parameter(hit.time),
+        frame.to_world(hit.normal).to_unit(),
+        material,
+    )
+    .with_uv(hit.u, hit.v)
+}
+
+fn angle_around_axis(point: Vector) -> f64 {
+    point.y.atan2(point.x).rem_euclid(2.0 * f64::consts::PI) / (2.0 * f64::consts::PI)
+}
+
+// Real roots of a t² + b t + c, in increasing order. Uses the form that avoids cancellation
+// between b and the square root.
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (first, second) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    if first < second {
+        vec![first, second]
+    } else {
+        vec![second, first]
+    }
+}
+
+// The largest real root of t³ + a t² + b t + c.
+fn largest_cubic_root(a: f64, b: f64, c: f64) -> f64 {
+    let q = (a * a - 3.0 * b) / 9.0;
+    let r = (2.0 * a * a * a - 9.0 * a * b + 27.0 * c) / 54.0;
+    let shift = a / 3.0;
+    if r * r < q * q * q {
+        // Of the three roots -2√q cos((θ + 2πk) / 3) - a/3, k = 1 gives the largest.
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        -2.0 * q.sqrt() * ((theta + 2.0 * f64::consts::PI) / 3.0).cos() - shift
+    } else {
+        let big_a = -r.signum() * (r.abs() + (r * r - q * q * q).sqrt()).cbrt();
+        let big_b = if big_a == 0.0 { 0.0 } else { q / big_a };
+        big_a + big_b - shift
+    }
+}
+
+// Real roots of t⁴ + a t³ + b t² + c t + d by Ferrari's method, each polished with a couple of
+// Newton steps on the original polynomial.
+fn solve_quartic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    // Substitute t = y - a/4 to drop the cubic term.
+    let a2 = a * a;
+    let p = b - 3.0 * a2 / 8.0;
+    let q = c - a * b / 2.0 + a2 * a / 8.0;
+    let r = d - a * c / 4.0 + a2 * b / 16.0 - 3.0 * a2 * a2 / 256.0;
+
+    let mut roots = Vec::with_capacity(4);
+    if q.abs() < 1e-12 {
+        // Biquadratic: a quadratic in y².
+        for z in solve_quadratic(1.0, p, r) {
+            if z >= 0.0 {
+                roots.push(z.sqrt());
+                roots.push(-z.sqrt());
+            }
+        }
+    } else {
+        // Split into two quadratics using a positive root of the resolvent cubic.
+        let m = largest_cubic_root(p, p * p / 4.0 - r, -q * q / 8.0);
+        if m <= 0.0 {
+            return vec![];
+        }
+        let s = (2.0 * m).sqrt();
+        roots.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+        roots.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+    }
+
+    let mut roots: Vec<f64> = roots
+        .into_iter()
+        .map(|y| {
+            let mut t = y - a / 4.0;
+            for _ in 0..2 {
+                let value = (((t + a) * t + b) * t + c) * t + d;
+                let slope = ((4.0 * t + 3.0 * a) * t + 2.0 * b) * t + c;
+                if slope != 0.0 {
+                    t -= value / slope;
+                }
+            }
+            t
+        })
+        .collect();
+    roots.sort_by(|x, y| x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal));
+    roots
+}
+
+// Hit on the flat end at `height`, a disc of `radius` facing along `facing` (±1).
+fn intersect_cap(
+    ray: Ray,
+    height: f64,
+    radius: f64,
+    facing: f64,
+    tmin: f64,
+    tmax: f64,
+) -> Option<LocalHit> {
+    if radius <= 0.0 || ray.direction.z == 0.0 {
+        return None;
+    }
+    let time = (height - ray.origin.z) / ray.direction.z;
+    if time <= tmin || time >= tmax {
+        return None;
+    }
+    let point = ray.point_at_parameter(time);
+    let distance = (point.x * point.x + point.y * point.y).sqrt();
+    if distance > radius {
+        return None;
+    }
+    Some(LocalHit {
+        time,
+        normal: Vector::new(0.0, 0.0, facing),
+        u: angle_around_axis(point),
+        v: distance / radius,
+    })
+}
+
+// A sphere of `radius` centered on the axis at `height`, keeping only hits on the side
+// `keep` says.
+fn intersect_end_sphere<F: Fn(f64) -> bool>(
+    ray: Ray,
+    height: f64,
+    radius: f64,
+    tmin: f64,
+    tmax: f64,
+    keep: F,
+) -> Option<LocalHit> {
+    let center = Vector::new(0.0, 0.0, height);
+    let offset = ray.origin - center;
+    let a = ray.direction.dot(ray.direction);
+    let b = 2.0 * offset.dot(ray.direction);
+    let c = offset.dot(offset) - radius * radius;
+    solve_quadratic(a, b, c)
+        .into_iter()
+        .filter(|&time| tmin < time && time < tmax)
+        .map(|time| (time, ray.point_at_parameter(time)))
+        .find(|&(_, point)| keep(point.z))
+        .map(|(time, point)| LocalHit {
+            time,
+            normal: (point - center) / radius,
+            u: angle_around_axis(point),
+            v: 0.0,
+        })
+}
+
+// Hits on the open side wall around the axis between heights 0 and `height`, whose radius
+// goes linearly from `start_radius` to `end_radius`.
+fn intersect_wall(
+    ray: Ray,
+    height: f64,
+    start_radius: f64,
+    end_radius: f64,
+    tmin: f64,
+    tmax: f64,
+) -> Option<LocalHit> {
+    let (o, d) = (ray.origin, ray.direction);
+    let slope = (end_radius - start_radius) / height;
+    let radius_at_origin = start_radius + slope * o.z;
+    let a = d.x * d.x + d.y * d.y - slope * slope * d.z * d.z;
+    let b = 2.0 * (o.x * d.x + o.y * d.y - slope * d.z * radius_at_origin);
+    let c = o.x * o.x + o.y * o.y - radius_at_origin * radius_at_origin;
+    solve_quadratic(a, b, c)
+        .into_iter()
+        .filter(|&time| tmin < time && time < tmax)
+        .map(|time| (time, ray.point_at_parameter(time)))
+        .find(|&(_, point)| (0.0..=height).contains(&point.z))
+        .map(|(time, point)| {
+            let radius = start_radius + slope * point.z;
+            LocalHit {
+                time,
+                normal: Vector::new(point.x, point.y, -slope * radius),
+                u: angle_around_axis(point),
+                v: point.z / height,
+            }
+        })
+}
+
+// A capped cylinder around the segment from `start` to `end`. u runs around the axis; v runs
+// along the side from `start` to `end`, and out from the center on the caps.
+pub struct Cylinder {
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(start: Vector, end: Vector, radius: f64, material: Arc<dyn Material>) -> Cylinder {
+        Cylinder {
+            frame: Frame::new(start, end - start),
+            height: (end - start).length(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl Collidable for Cylinder {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let local = self.frame.ray(ray);
+        let hit = intersect_wall(local, self.height, self.radius, self.radius, tmin, tmax);
+        let hit = closest(
+            hit,
+            intersect_cap(local, 0.0, self.radius, -1.0, tmin, tmax),
+        );
+        let hit = closest(
+            hit,
+            intersect_cap(local, self.height, self.radius, 1.0, tmin, tmax),
+        )?;
+        Some(collision(&self.frame, ray, hit, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.frame
+            .circle_bounds(0.0, self.radius)
+            .union(self.frame.circle_bounds(self.height, self.radius))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+// A capped cone, or a frustum when both radii are positive, around the segment from `start`
+// to `end`. Its uvs follow `Cylinder`.
+pub struct Cone {
+    frame: Frame,
+    height: f64,
+    start_radius: f64,
+    end_radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(
+        start: Vector,
+        end: Vector,
+        start_radius: f64,
+        end_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Cone {
+        Cone {
+            frame: Frame::new(start, end - start),
+            height: (end - start).length(),
+            start_radius,
+            end_radius,
+            material,
+        }
+    }
+}
+
+impl Collidable for Cone {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let local = self.frame.ray(ray);
+        let hit = intersect_wall(
+            local,
+            self.height,
+            self.start_radius,
+            self.end_radius,
+            tmin,
+            tmax,
+        );
+        let hit = closest(
+            hit,
+            intersect_cap(local, 0.0, self.start_radius, -1.0, tmin, tmax),
+        );
+        let hit = closest(
+            hit,
+            intersect_cap(local, self.height, self.end_radius, 1.0, tmin, tmax),
+        )?;
+        Some(collision(&self.frame, ray, hit, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.frame
+            .circle_bounds(0.0, self.start_radius)
+            .union(self.frame.circle_bounds(self.height, self.end_radius))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+// Every point within `radius` of the segment from `start` to `end`: a cylinder closed by two
+// hemispheres. u runs around the axis and v from the tip beyond `start` to the one beyond
+// `end`.
+pub struct Capsule {
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Capsule {
+    pub fn new(start: Vector, end: Vector, radius: f64, material: Arc<dyn Material>) -> Capsule {
+        // With coincident ends this is a sphere, and any axis will do.
+        let axis = if (end - start).length() > 0.0 {
+            end - start
+        } else {
+            Vector::new(0.0, 0.0, 1.0)
+        };
+        Capsule {
+            frame: Frame::new(start, axis),
+            height: (end - start).length(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl Collidable for Capsule {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let local = self.frame.ray(ray);
+        let height = self.height;
+        let hit = if height > 0.0 {
+            intersect_wall(local, height, self.radius, self.radius, tmin, tmax)
+        } else {
+            None
+        };
+        let hit = closest(
+            hit,
+            intersect_end_sphere(local, 0.0, self.radius, tmin, tmax, |z| z <= 0.0),
+        );
+        let mut hit = closest(
+            hit,
+            intersect_end_sphere(local, height, self.radius, tmin, tmax, |z| z >= height),
+        )?;
+        let point = local.point_at_parameter(hit.time);
+        hit.v = (point.z + self.radius) / (height + 2.0 * self.radius);
+        Some(collision(&self.frame, ray, hit, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vector::new(self.radius, self.radius, self.radius);
+        let start = self.frame.origin;
+        let end = start + self.frame.z * self.height;
+        Aabb::new(start - radius, start + radius).union(Aabb::new(end - radius, end + radius))
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+// A ring-shaped tube: the points `minor_radius` away from a circle of `major_radius` around
+// `center`, perpendicular to `axis`. u runs around the ring and v around the tube.
+pub struct Torus {
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Vector,
+        axis: Vector,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Torus {
+        Torus {
+            frame: Frame::new(center, axis),
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Collidable for Torus {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let local = self.frame.ray(ray);
+        let length = local.direction.length();
+        let direction = local.direction / length;
+
+        // Start the quartic from where the ray meets the bounding sphere, since its
+        // coefficients lose precision quickly far away from the torus.
+        let outer = self.major_radius + self.minor_radius;
+        let b = local.origin.dot(direction);
+        let c = local.origin.dot(local.origin) - outer * outer;
+        let discriminant = b * b - c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+        let start = (-b - discriminant.sqrt()).max(0.0);
+        let origin = local.origin + direction * start;
+
+        let (big_r2, small_r2) = (
+            self.major_radius * self.major_radius,
+            self.minor_radius * self.minor_radius,
+        );
+        let p = origin.dot(direction);
+        let q = origin.dot(origin) + big_r2 - small_r2;
+        let planar_dd = direction.x * direction.x + direction.y * direction.y;
+        let planar_od = origin.x * direction.x + origin.y * direction.y;
+        let planar_oo = origin.x * origin.x + origin.y * origin.y;
+        let roots = solve_quartic(
+            4.0 * p,
+            4.0 * p * p + 2.0 * q - 4.0 * big_r2 * planar_dd,
+            4.0 * p * q - 8.0 * big_r2 * planar_od,
+            q * q - 4.0 * big_r2 * planar_oo,
+        );
+
+        let time = roots
+            .into_iter()
+            .map(|distance| (start + distance) / length)
+            .find(|&time| tmin < time && time < tmax)?;
+        let point = local.point_at_parameter(time);
+        let planar = (point.x * point.x + point.y * point.y).sqrt();
+        let sum = point.dot(point) + big_r2 - small_r2;
+        let normal = point * (4.0 * sum) - Vector::new(point.x, point.y, 0.0) * (8.0 * big_r2);
+        let tube_angle = point.z.atan2(planar - self.major_radius);
+        let hit = LocalHit {
+            time,
+            normal,
+            u: angle_around_axis(point),
+            v: tube_angle.rem_euclid(2.0 * f64::consts::PI) / (2.0 * f64::consts::PI),
+        };
+        Some(collision(&self.frame, ray, hit, &*self.material))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let minor = Vector::new(self.minor_radius, self.minor_radius, self.minor_radius);
+        let reach = self.frame.circle_reach(self.major_radius) + minor;
+        Aabb::new(self.frame.origin - reach, self.frame.origin + reach)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(found: Vec<f64>, expected: &[f64]) {
+        assert_eq!(found.len(), expected.len(), "{:?} vs {:?}", found, expected);
+        for (found, expected) in found.iter().zip(expected) {
+            assert!(
+                (found - expected).abs() < 1e-9,
+                "{:?} vs {:?}",
+                found,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        // (t - 1)(t - 3)
+        assert_roots(solve_quadratic(1.0, -4.0, 3.0), &[1.0, 3.0]);
+        // 2(t + 2)(t - 0.5)
+        assert_roots(solve_quadratic(2.0, 3.0, -2.0), &[-2.0, 0.5]);
+        assert_roots(solve_quadratic(0.0, 2.0, -1.0), &[0.5]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn largest_cubic_root_with_three_real_roots() {
+        // (t - 1)(t - 2)(t - 5)
+        assert_roots(vec![largest_cubic_root(-8.0, 17.0, -10.0)], &[5.0]);
+        // (t + 4)(t + 3)(t + 1)
+        assert_roots(vec![largest_cubic_root(8.0, 19.0, 12.0)], &[-1.0]);
+    }
+
+    #[test]
+    fn largest_cubic_root_with_one_real_root() {
+        // (t - 2)(t² + 1)
+        assert_roots(vec![largest_cubic_root(-2.0, 1.0, -2.0)], &[2.0]);
+        // (t + 1)³
+        assert_roots(vec![largest_cubic_root(3.0, 3.0, 1.0)], &[-1.0]);
+    }
+
+    #[test]
+    fn quartic_with_four_real_roots() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        assert_roots(
+            solve_quartic(-10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (t + 0.5)(t - 0.25)(t - 2)(t - 7)
+        assert_roots(
+            solve_quartic(-8.75, 11.625, 4.625, -1.75),
+            &[-0.5, 0.25, 2.0, 7.0],
+        );
+    }
+
+    #[test]
+    fn biquadratic_quartic() {
+        // (t² - 1)(t² - 4)
+        assert_roots(solve_quartic(0.0, -5.0, 0.0, 4.0), &[-2.0, -1.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn quartic_with_two_real_roots() {
+        // (t² + 1)(t - 1)(t - 3)
+        assert_roots(solve_quartic(-4.0, 4.0, -4.0, 3.0), &[1.0, 3.0]);
+    }
+
+    #[test]
+    fn quartic_without_real_roots() {
+        // (t² + 1)(t² + 4)
+        assert_roots(solve_quartic(0.0, 5.0, 0.0, 4.0), &[]);
+        // (t² + 2t + 2)(t² - 2t + 5)
+        assert_roots(solve_quartic(0.0, 3.0, 6.0, 10.0), &[]);
+    }
+}
@@ -4,14 +4,20 @@ use crate::vector::Vector;
 pub struct Ray {
     pub origin: Vector,
     pub direction: Vector,
+    // The moment the ray was cast, for motion blur. Everything it spawns shares it.
+    pub time: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Vector, direction: Vector) -> Ray {
-        Ray { origin, direction }
+    pub fn new(origin: Vector, direction: Vector, time: f64) -> Ray {
+        Ray {
+            origin,
+            direction,
+            time,
+        }
     }
 
-    pub fn point_at_parameter(self: &Self, t: f64) -> Vector {
+    pub fn point_at_parameter(&self, t: f64) -> Vector {
         self.origin + self.direction * t
     }
 }
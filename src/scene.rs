@@ -0,0 +1,1572 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use toml::Spanned;
+
+use crate::aperture::{Aperture, Circle, ImageAperture, Polygon};
+use crate::bvh::Bvh;
+use crate::camera::Camera;
+use crate::collision::Collidable;
+use crate::color::Color;
+use crate::csg::{Csg, CsgOperation};
+use crate::environment::{Constant, Environment, EnvironmentMap, Gradient};
+use crate::grid::DensityGrid;
+use crate::instance::Transformed;
+use crate::light::DirectionalLight;
+use crate::material::{Dialectic, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::mesh::Mesh;
+use crate::obj::load_obj;
+use crate::projection::{Equirectangular, Fisheye, Orthographic, Perspective, Projection};
+use crate::quadric::{Capsule, Cone, Cylinder, Torus};
+use crate::shape::{AxisAlignedBox, Disk, MovingSphere, Plane, Quad, Sphere, Triangle};
+use crate::sky::Sky;
+use crate::stereo::{Stereo, StereoLayout};
+use crate::texture::{Checker, ImageTexture, Noise, SolidColor, Texture};
+use crate::transform::Placement;
+use crate::vector::Vector;
+use crate::volume::{ConstantMedium, Fog, GridMedium};
+use crate::world::World;
+
+pub struct Scene {
+    pub camera: Camera,
+    pub world: World,
+    pub environment: Arc<dyn Environment>,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Invalid {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "{}", error),
+            SceneError::Invalid {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+    camera: Spanned<CameraDescription>,
+    environment: Option<Spanned<EnvironmentDescription>>,
+    sun: Option<Spanned<SunDescription>>,
+    fog: Option<Spanned<FogDescription>>,
+    #[serde(default)]
+    textures: BTreeMap<String, Spanned<TextureDescription>>,
+    #[serde(default)]
+    materials: BTreeMap<String, Spanned<MaterialDescription>>,
+    #[serde(default)]
+    models: BTreeMap<String, Spanned<ModelDescription>>,
+    #[serde(default)]
+    shapes: Vec<Spanned<ShapeDescription>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDescription {
+    origin: [f64; 3],
+    target: [f64; 3],
+    #[serde(default = "default_up")]
+    up: [f64; 3],
+    // Vertical for perspective projections, and across the image circle for fisheye ones.
+    field_of_view: Option<f64>,
+    projection: Option<ProjectionDescription>,
+    #[serde(default = "default_aspect_ratio")]
+    aspect_ratio: f64,
+    #[serde(default)]
+    aperture: f64,
+    aperture_shape: Option<ApertureDescription>,
+    // Defaults to the distance to `target`.
+    focus_distance: Option<f64>,
+    // A point in the frame, as fractions of its width and height from the top left, to focus
+    // on whatever is seen there.
+    autofocus: Option<[f64; 2]>,
+    #[serde(default)]
+    shutter_open: f64,
+    #[serde(default)]
+    shutter_close: f64,
+    stereo: Option<StereoDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ProjectionDescription {
+    Perspective,
+    // Shows a view `height` units tall.
+    Orthographic { height: f64 },
+    Fisheye,
+    Equirectangular,
+}
+
+// `convergence` defaults to the distance to the camera's target; `inf` keeps the eyes
+// parallel.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StereoDescription {
+    #[serde(default = "default_interocular")]
+    interocular: f64,
+    convergence: Option<f64>,
+    #[serde(default = "default_stereo_layout")]
+    layout: LayoutDescription,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LayoutDescription {
+    SideBySide,
+    TopBottom,
+}
+
+impl From<LayoutDescription> for StereoLayout {
+    fn from(description: LayoutDescription) -> StereoLayout {
+        match description {
+            LayoutDescription::SideBySide => StereoLayout::SideBySide,
+            LayoutDescription::TopBottom => StereoLayout::TopBottom,
+        }
+    }
+}
+
+// The average adult's, in metres.
+fn default_interocular() -> f64 {
+    0.064
+}
+
+fn default_stereo_layout() -> LayoutDescription {
+    LayoutDescription::SideBySide
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ApertureDescription {
+    Circle,
+    // A regular polygon with one corner pointing `rotation` degrees counterclockwise from the
+    // camera's right.
+    Polygon {
+        sides: usize,
+        #[serde(default)]
+        rotation: f64,
+    },
+    // A grayscale mask; brighter pixels let more light through.
+    Image {
+        path: String,
+    },
+}
+
+fn default_up() -> [f64; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_aspect_ratio() -> f64 {
+    4.0 / 3.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum EnvironmentDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    Gradient {
+        bottom: [f64; 3],
+        top: [f64; 3],
+    },
+    // An equirectangular panorama, turned by `rotation` degrees around the vertical axis.
+    Image {
+        path: String,
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+        #[serde(default)]
+        rotation: f64,
+    },
+    Sky {
+        sun_direction: [f64; 3],
+        #[serde(default = "default_turbidity")]
+        turbidity: f64,
+        #[serde(default = "default_ground_albedo")]
+        ground_albedo: [f64; 3],
+        #[serde(default = "default_intensity")]
+        intensity: f64,
+    },
+}
+
+fn default_intensity() -> f64 {
+    1.0
+}
+
+fn default_turbidity() -> f64 {
+    3.0
+}
+
+fn default_ground_albedo() -> [f64; 3] {
+    [0.3, 0.3, 0.3]
+}
+
+// A directional light; without a `direction` it follows the sun of a `sky` environment.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SunDescription {
+    direction: Option<[f64; 3]>,
+    irradiance: [f64; 3],
+    #[serde(default = "default_sun_radius")]
+    angular_radius: f64,
+}
+
+fn default_sun_radius() -> f64 {
+    0.27
+}
+
+// Homogeneous fog over the whole scene, below `ceiling` if one is given.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FogDescription {
+    density: f64,
+    albedo: [f64; 3],
+    ceiling: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDescription {
+    Solid {
+        color: [f64; 3],
+    },
+    Checker {
+        even: [f64; 3],
+        odd: [f64; 3],
+        #[serde(default = "default_checker_scale")]
+        scale: f64,
+    },
+    Image {
+        path: String,
+    },
+    Noise {
+        color: [f64; 3],
+        #[serde(default = "default_noise_scale")]
+        scale: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+fn default_checker_scale() -> f64 {
+    1.0
+}
+
+fn default_noise_scale() -> f64 {
+    4.0
+}
+
+// Lambertian, metal and isotropic materials are colored by either a constant `albedo` or a
+// named texture.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDescription {
+    Lambertian {
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+    },
+    Metal {
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+        #[serde(default)]
+        fuzz: f64,
+    },
+    Dialectic {
+        refraction_index: f64,
+    },
+    // The phase function for `medium` shapes.
+    Isotropic {
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+    },
+    DiffuseLight {
+        emit: [f64; 3],
+    },
+}
+
+// An OBJ file loaded once and placed any number of times by `instance` shapes.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModelDescription {
+    path: String,
+    material: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ShapeDescription {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    // Moves in a straight line from `start_center` to `end_center` between the two times.
+    MovingSphere {
+        start_center: [f64; 3],
+        end_center: [f64; 3],
+        #[serde(default)]
+        start_time: f64,
+        #[serde(default = "default_end_time")]
+        end_time: f64,
+        radius: f64,
+        material: String,
+    },
+    Plane {
+        point: [f64; 3],
+        normal: [f64; 3],
+        material: String,
+    },
+    Quad {
+        corner: [f64; 3],
+        edge_u: [f64; 3],
+        edge_v: [f64; 3],
+        material: String,
+    },
+    Disk {
+        center: [f64; 3],
+        normal: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    #[serde(rename = "box")]
+    AxisAlignedBox {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: String,
+    },
+    Cylinder {
+        start: [f64; 3],
+        end: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    // A pointed cone by default; a positive `end_radius` makes it a frustum.
+    Cone {
+        start: [f64; 3],
+        end: [f64; 3],
+        start_radius: f64,
+        #[serde(default)]
+        end_radius: f64,
+        material: String,
+    },
+    Capsule {
+        start: [f64; 3],
+        end: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Torus {
+        center: [f64; 3],
+        axis: [f64; 3],
+        major_radius: f64,
+        minor_radius: f64,
+        material: String,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[[f64; 2]; 3]>,
+        material: String,
+    },
+    // Combines two closed shapes, given as inline tables.
+    Csg {
+        operation: OperationDescription,
+        left: Box<ShapeDescription>,
+        right: Box<ShapeDescription>,
+    },
+    // A participating medium filling a closed shape, given as an inline table. The shape's
+    // own material is ignored in favour of `material`, the medium's phase function.
+    Medium {
+        boundary: Box<ShapeDescription>,
+        density: f64,
+        material: String,
+    },
+    // A medium whose density comes from a grid file, stretched over the box from `min` to
+    // `max` and scaled by `density`.
+    GridMedium {
+        path: String,
+        min: [f64; 3],
+        max: [f64; 3],
+        #[serde(default = "default_density")]
+        density: f64,
+        material: String,
+    },
+    Obj {
+        path: String,
+        material: Option<String>,
+    },
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        #[serde(default)]
+        normals: Vec<[f64; 3]>,
+        #[serde(default)]
+        uvs: Vec<[f64; 2]>,
+        faces: Vec<[usize; 3]>,
+        material: String,
+    },
+    // Scaled first, then rotated around the x, y and z axes in turn (in degrees), then
+    // translated.
+    Instance {
+        model: String,
+        #[serde(default)]
+        translate: [f64; 3],
+        #[serde(default)]
+        rotate: [f64; 3],
+        #[serde(default = "default_scale")]
+        scale: [f64; 3],
+        motion: Option<MotionDescription>,
+    },
+}
+
+// Where an instance ends up by `end_time`, having started at its own placement at
+// `start_time`. Parts left out stay as they started.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MotionDescription {
+    translate: Option<[f64; 3]>,
+    rotate: Option<[f64; 3]>,
+    scale: Option<[f64; 3]>,
+    #[serde(default)]
+    start_time: f64,
+    #[serde(default = "default_end_time")]
+    end_time: f64,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum OperationDescription {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl From<OperationDescription> for CsgOperation {
+    fn from(description: OperationDescription) -> CsgOperation {
+        match description {
+            OperationDescription::Union => CsgOperation::Union,
+            OperationDescription::Intersection => CsgOperation::Intersection,
+            OperationDescription::Difference => CsgOperation::Difference,
+        }
+    }
+}
+
+fn default_density() -> f64 {
+    1.0
+}
+
+fn default_end_time() -> f64 {
+    1.0
+}
+
+fn default_scale() -> [f64; 3] {
+    [1.0, 1.0, 1.0]
+}
+
+fn vector(value: [f64; 3]) -> Vector {
+    Vector::new(value[0], value[1], value[2])
+}
+
+fn color(value: [f64; 3]) -> Color {
+    Color::new(value[0], value[1], value[2])
+}
+
+fn uv(value: [f64; 2]) -> (f64, f64) {
+    (value[0], value[1])
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    directory: &'a Path,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    models: HashMap<String, Vec<Arc<Mesh>>>,
+}
+
+impl<'a> Parser<'a> {
+    fn error<M: Into<String>>(&self, span: Range<usize>, message: M) -> SceneError {
+        let before = &self.source[..span.start.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |index| index + 1) + 1;
+        SceneError::Invalid {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    fn camera(&self, description: Spanned<CameraDescription>) -> Result<Camera, SceneError> {
+        let span = description.span();
+        let description = description.into_inner();
+
+        let origin = vector(description.origin);
+        let target = vector(description.target);
+        if (origin - target).length() == 0.0 {
+            return Err(self.error(span, "camera origin and target must differ"));
+        }
+        let projection = self.projection(
+            span.clone(),
+            description
+                .projection
+                .unwrap_or(ProjectionDescription::Perspective),
+            description.field_of_view,
+        )?;
+        if description.aspect_ratio <= 0.0 {
+            return Err(self.error(
+                span,
+                format!(
+                    "camera aspect_ratio must be positive, got {}",
+                    description.aspect_ratio
+                ),
+            ));
+        }
+
+        if description.shutter_close < description.shutter_open {
+            return Err(self.error(
+                span,
+                format!(
+                    "camera shutter_close must not be before shutter_open, got {} and {}",
+                    description.shutter_close, description.shutter_open
+                ),
+            ));
+        }
+
+        if description.aperture < 0.0 {
+            return Err(self.error(
+                span,
+                format!(
+                    "camera aperture must not be negative, got {}",
+                    description.aperture
+                ),
+            ));
+        }
+
+        let mut camera = Camera::projected(
+            origin,
+            target,
+            vector(description.up),
+            projection,
+            description.aspect_ratio,
+            description.aperture,
+        )
+        .with_shutter(description.shutter_open, description.shutter_close);
+
+        if let Some(distance) = description.focus_distance {
+            if distance <= 0.0 {
+                return Err(self.error(
+                    span,
+                    format!("camera focus_distance must be positive, got {}", distance),
+                ));
+            }
+            camera = camera.with_focus_distance(distance);
+        }
+        if let Some([x, y]) = description.autofocus {
+            if !(0.0..=1.0).contains(&x) || !(0.0..=1.0).contains(&y) {
+                return Err(self.error(
+                    span,
+                    format!(
+                        "camera autofocus must be within the frame, between 0 and 1, got [{}, {}]",
+                        x, y
+                    ),
+                ));
+            }
+            camera = camera.with_autofocus(x, y);
+        }
+        if let Some(shape) = description.aperture_shape {
+            camera = camera.with_aperture(self.aperture(span.clone(), shape)?);
+        }
+        if let Some(stereo) = description.stereo {
+            if !stereo.interocular.is_finite() || stereo.interocular < 0.0 {
+                return Err(self.error(
+                    span,
+                    format!(
+                        "camera stereo interocular must not be negative, got {}",
+                        stereo.interocular
+                    ),
+                ));
+            }
+            let convergence = stereo
+                .convergence
+                .unwrap_or_else(|| (origin - target).length());
+            if convergence <= 0.0 {
+                return Err(self.error(
+                    span,
+                    format!(
+                        "camera stereo convergence must be positive, got {}",
+                        convergence
+                    ),
+                ));
+            }
+            camera = camera.with_stereo(
+                Stereo::new(stereo.interocular, convergence).with_layout(stereo.layout.into()),
+            );
+        }
+        Ok(camera)
+    }
+
+    fn projection(
+        &self,
+        span: Range<usize>,
+        description: ProjectionDescription,
+        field_of_view: Option<f64>,
+    ) -> Result<Arc<dyn Projection>, SceneError> {
+        Ok(match description {
+            ProjectionDescription::Perspective => {
+                let field_of_view = field_of_view
+                    .ok_or_else(|| self.error(span.clone(), "camera field_of_view is required"))?;
+                if field_of_view <= 0.0 || field_of_view >= 180.0 {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "camera field_of_view must be between 0 and 180 degrees, got {}",
+                            field_of_view
+                        ),
+                    ));
+                }
+                Arc::new(Perspective::new(field_of_view))
+            }
+            ProjectionDescription::Orthographic { height } => {
+                if height <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("orthographic height must be positive, got {}", height),
+                    ));
+                }
+                Arc::new(Orthographic::new(height))
+            }
+            ProjectionDescription::Fisheye => {
+                let field_of_view = field_of_view.unwrap_or(180.0);
+                if field_of_view <= 0.0 || field_of_view > 360.0 {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "fisheye field_of_view must be between 0 and 360 degrees, got {}",
+                            field_of_view
+                        ),
+                    ));
+                }
+                Arc::new(Fisheye::new(field_of_view))
+            }
+            ProjectionDescription::Equirectangular => Arc::new(Equirectangular),
+        })
+    }
+
+    fn aperture(
+        &self,
+        span: Range<usize>,
+        description: ApertureDescription,
+    ) -> Result<Arc<dyn Aperture>, SceneError> {
+        Ok(match description {
+            ApertureDescription::Circle => Arc::new(Circle),
+            ApertureDescription::Polygon { sides, rotation } => {
+                if sides < 3 {
+                    return Err(self.error(
+                        span,
+                        format!("aperture polygon needs at least 3 sides, got {}", sides),
+                    ));
+                }
+                Arc::new(Polygon::new(sides, rotation))
+            }
+            ApertureDescription::Image { path } => {
+                let aperture =
+                    ImageAperture::load(self.directory.join(&path)).map_err(|error| {
+                        self.error(span.clone(), format!("aperture {}: {}", path, error))
+                    })?;
+                if !aperture.is_open() {
+                    return Err(
+                        self.error(span, format!("aperture image {} is completely black", path))
+                    );
+                }
+                Arc::new(aperture)
+            }
+        })
+    }
+
+    // The environment, along with the direction of its sun if it has one.
+    fn environment(
+        &self,
+        description: Spanned<EnvironmentDescription>,
+    ) -> Result<(Arc<dyn Environment>, Option<Vector>), SceneError> {
+        let span = description.span();
+        let mut sun_direction = None;
+        let environment: Arc<dyn Environment> = match description.into_inner() {
+            EnvironmentDescription::Solid { color: solid } => Arc::new(Constant::new(color(solid))),
+            EnvironmentDescription::Gradient { bottom, top } => {
+                Arc::new(Gradient::new(color(bottom), color(top)))
+            }
+            EnvironmentDescription::Image {
+                path,
+                intensity,
+                rotation,
+            } => {
+                if intensity < 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "environment intensity must not be negative, got {}",
+                            intensity
+                        ),
+                    ));
+                }
+                let map = EnvironmentMap::load(self.directory.join(&path))
+                    .map_err(|error| self.error(span, format!("{}: {}", path, error)))?;
+                Arc::new(map.with_intensity(intensity).with_rotation(rotation))
+            }
+            EnvironmentDescription::Sky {
+                sun_direction: direction,
+                turbidity,
+                ground_albedo,
+                intensity,
+            } => {
+                let direction = vector(direction);
+                if direction.length() == 0.0 || direction.y < 0.0 {
+                    return Err(self.error(
+                        span,
+                        "sky sun_direction must be non-zero and not below the horizon",
+                    ));
+                }
+                if !(1.0..=20.0).contains(&turbidity) {
+                    return Err(self.error(
+                        span,
+                        format!("sky turbidity must be between 1 and 20, got {}", turbidity),
+                    ));
+                }
+                let sky =
+                    Sky::new(direction, turbidity, color(ground_albedo)).with_intensity(intensity);
+                sun_direction = Some(sky.sun_direction());
+                Arc::new(sky)
+            }
+        };
+        Ok((environment, sun_direction))
+    }
+
+    fn sun(
+        &self,
+        description: Spanned<SunDescription>,
+        sky_sun: Option<Vector>,
+    ) -> Result<DirectionalLight, SceneError> {
+        let span = description.span();
+        let description = description.into_inner();
+        let direction = match (description.direction, sky_sun) {
+            (Some(direction), _) => vector(direction),
+            (None, Some(direction)) => direction,
+            (None, None) => {
+                return Err(self.error(
+                    span,
+                    "sun needs a direction unless the environment is a sky",
+                ))
+            }
+        };
+        if direction.length() == 0.0 {
+            return Err(self.error(span, "sun direction must be non-zero"));
+        }
+        if description.irradiance.iter().any(|&channel| channel < 0.0) {
+            return Err(self.error(span, "sun irradiance must not be negative"));
+        }
+        if !(0.0..90.0).contains(&description.angular_radius) {
+            return Err(self.error(
+                span,
+                format!(
+                    "sun angular_radius must be between 0 and 90 degrees, got {}",
+                    description.angular_radius
+                ),
+            ));
+        }
+        Ok(
+            DirectionalLight::new(direction, color(description.irradiance))
+                .with_angular_radius(description.angular_radius),
+        )
+    }
+
+    fn fog(&self, description: Spanned<FogDescription>) -> Result<Fog, SceneError> {
+        let span = description.span();
+        let description = description.into_inner();
+        if description.density <= 0.0 {
+            return Err(self.error(
+                span,
+                format!("fog density must be positive, got {}", description.density),
+            ));
+        }
+        if description.albedo.iter().any(|&channel| channel < 0.0) {
+            return Err(self.error(span, "fog albedo must not be negative"));
+        }
+        let fog = Fog::new(description.density, color(description.albedo));
+        Ok(match description.ceiling {
+            Some(ceiling) => fog.with_ceiling(ceiling),
+            None => fog,
+        })
+    }
+
+    fn texture(
+        &self,
+        name: &str,
+        description: Spanned<TextureDescription>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        let span = description.span();
+        let texture: Arc<dyn Texture> = match description.into_inner() {
+            TextureDescription::Solid { color: solid } => Arc::new(SolidColor::new(color(solid))),
+            TextureDescription::Checker { even, odd, scale } => {
+                if scale <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "texture `{}` must have a positive scale, got {}",
+                            name, scale
+                        ),
+                    ));
+                }
+                Arc::new(Checker::new(
+                    Arc::new(SolidColor::new(color(even))),
+                    Arc::new(SolidColor::new(color(odd))),
+                    scale,
+                ))
+            }
+            TextureDescription::Image { path } => {
+                let image = ImageTexture::load(self.directory.join(&path)).map_err(|error| {
+                    self.error(span, format!("texture `{}`: {}: {}", name, path, error))
+                })?;
+                Arc::new(image)
+            }
+            TextureDescription::Noise {
+                color: veins,
+                scale,
+                seed,
+            } => Arc::new(Noise::new(color(veins), scale, seed)),
+        };
+        Ok(texture)
+    }
+
+    // The texture for a material given either a constant `albedo` or the name of a texture.
+    fn albedo(
+        &self,
+        span: Range<usize>,
+        name: &str,
+        albedo: Option<[f64; 3]>,
+        texture: Option<String>,
+    ) -> Result<Arc<dyn Texture>, SceneError> {
+        match (albedo, texture) {
+            (Some(albedo), None) => Ok(Arc::new(SolidColor::new(color(albedo)))),
+            (None, Some(texture)) => self
+                .textures
+                .get(&texture)
+                .cloned()
+                .ok_or_else(|| self.error(span, format!("unknown texture `{}`", texture))),
+            _ => Err(self.error(
+                span,
+                format!(
+                    "material `{}` needs exactly one of `albedo` or `texture`",
+                    name
+                ),
+            )),
+        }
+    }
+
+    fn material(
+        &self,
+        name: &str,
+        description: Spanned<MaterialDescription>,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        let span = description.span();
+        let material: Arc<dyn Material> = match description.into_inner() {
+            MaterialDescription::Lambertian { albedo, texture } => Arc::new(Lambertian::textured(
+                self.albedo(span, name, albedo, texture)?,
+            )),
+            MaterialDescription::Metal {
+                albedo,
+                texture,
+                fuzz,
+            } => {
+                if fuzz < 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("material `{}` has negative fuzz {}", name, fuzz),
+                    ));
+                }
+                Arc::new(Metal::textured(
+                    self.albedo(span, name, albedo, texture)?,
+                    fuzz,
+                ))
+            }
+            MaterialDescription::Dialectic { refraction_index } => {
+                if refraction_index <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "material `{}` must have a positive refraction_index, got {}",
+                            name, refraction_index
+                        ),
+                    ));
+                }
+                Arc::new(Dialectic::new(refraction_index))
+            }
+            MaterialDescription::Isotropic { albedo, texture } => Arc::new(Isotropic::textured(
+                self.albedo(span, name, albedo, texture)?,
+            )),
+            MaterialDescription::DiffuseLight { emit } => {
+                if emit.iter().any(|&channel| channel < 0.0) {
+                    return Err(
+                        self.error(span, format!("material `{}` has negative emission", name))
+                    );
+                }
+                Arc::new(DiffuseLight::new(color(emit)))
+            }
+        };
+        Ok(material)
+    }
+
+    fn lookup_material(
+        &self,
+        span: Range<usize>,
+        name: &str,
+    ) -> Result<Arc<dyn Material>, SceneError> {
+        self.materials
+            .get(name)
+            .cloned()
+            .ok_or_else(|| self.error(span, format!("unknown material `{}`", name)))
+    }
+
+    fn load_model(
+        &self,
+        span: Range<usize>,
+        path: &str,
+        material: Option<String>,
+    ) -> Result<Vec<Mesh>, SceneError> {
+        let material = match material {
+            Some(material) => self.lookup_material(span.clone(), &material)?,
+            None => Arc::new(Lambertian::new(Vector::unit() * 0.5)),
+        };
+        let groups = load_obj(self.directory.join(path), material)
+            .map_err(|error| self.error(span, error.to_string()))?;
+        Ok(groups.into_iter().map(|group| group.mesh).collect())
+    }
+
+    fn model(&self, description: Spanned<ModelDescription>) -> Result<Vec<Arc<Mesh>>, SceneError> {
+        let span = description.span();
+        let description = description.into_inner();
+        let meshes = self.load_model(span, &description.path, description.material)?;
+        Ok(meshes.into_iter().map(Arc::new).collect())
+    }
+
+    fn shape(
+        &self,
+        description: Spanned<ShapeDescription>,
+    ) -> Result<Vec<Box<dyn Collidable>>, SceneError> {
+        let span = description.span();
+        self.build_shape(span, description.into_inner())
+    }
+
+    // Combines everything a description produces into one object, for the operands of
+    // `csg` shapes.
+    fn single_shape(
+        &self,
+        span: Range<usize>,
+        description: ShapeDescription,
+    ) -> Result<Box<dyn Collidable>, SceneError> {
+        let mut shapes = self.build_shape(span, description)?;
+        if shapes.len() == 1 {
+            Ok(shapes.remove(0))
+        } else {
+            Ok(Box::new(Bvh::new(shapes)))
+        }
+    }
+
+    // Nested descriptions have no position of their own, so errors in them are reported at
+    // the enclosing top-level shape.
+    fn build_shape(
+        &self,
+        span: Range<usize>,
+        description: ShapeDescription,
+    ) -> Result<Vec<Box<dyn Collidable>>, SceneError> {
+        let shape: Box<dyn Collidable> = match description {
+            ShapeDescription::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                if radius <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("sphere radius must be positive, got {}", radius),
+                    ));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Sphere::new(vector(center), radius, material))
+            }
+            ShapeDescription::MovingSphere {
+                start_center,
+                end_center,
+                start_time,
+                end_time,
+                radius,
+                material,
+            } => {
+                if radius <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("sphere radius must be positive, got {}", radius),
+                    ));
+                }
+                if end_time < start_time {
+                    return Err(self.error(span, "sphere end_time must not be before start_time"));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(MovingSphere::new(
+                    vector(start_center),
+                    vector(end_center),
+                    start_time,
+                    end_time,
+                    radius,
+                    material,
+                ))
+            }
+            ShapeDescription::Plane {
+                point,
+                normal,
+                material,
+            } => {
+                if vector(normal).length() == 0.0 {
+                    return Err(self.error(span, "plane normal must be non-zero"));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Plane::new(vector(point), vector(normal), material))
+            }
+            ShapeDescription::Quad {
+                corner,
+                edge_u,
+                edge_v,
+                material,
+            } => {
+                if vector(edge_u).cross(vector(edge_v)).length() == 0.0 {
+                    return Err(self.error(span, "quad edges must be non-zero and not parallel"));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Quad::new(
+                    vector(corner),
+                    vector(edge_u),
+                    vector(edge_v),
+                    material,
+                ))
+            }
+            ShapeDescription::Disk {
+                center,
+                normal,
+                radius,
+                material,
+            } => {
+                if vector(normal).length() == 0.0 {
+                    return Err(self.error(span, "disk normal must be non-zero"));
+                }
+                if radius <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("disk radius must be positive, got {}", radius),
+                    ));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Disk::new(vector(center), vector(normal), radius, material))
+            }
+            ShapeDescription::AxisAlignedBox { min, max, material } => {
+                if (0..3).any(|axis| min[axis] >= max[axis]) {
+                    return Err(self.error(span, "box min must be below max along every axis"));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(AxisAlignedBox::new(vector(min), vector(max), material))
+            }
+            ShapeDescription::Cylinder {
+                start,
+                end,
+                radius,
+                material,
+            } => {
+                if (vector(end) - vector(start)).length() == 0.0 {
+                    return Err(self.error(span, "cylinder start and end must differ"));
+                }
+                if radius <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("cylinder radius must be positive, got {}", radius),
+                    ));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Cylinder::new(vector(start), vector(end), radius, material))
+            }
+            ShapeDescription::Cone {
+                start,
+                end,
+                start_radius,
+                end_radius,
+                material,
+            } => {
+                if (vector(end) - vector(start)).length() == 0.0 {
+                    return Err(self.error(span, "cone start and end must differ"));
+                }
+                if start_radius < 0.0 || end_radius < 0.0 || start_radius + end_radius == 0.0 {
+                    return Err(self.error(
+                        span,
+                        "cone radii must not be negative, and at least one must be positive",
+                    ));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Cone::new(
+                    vector(start),
+                    vector(end),
+                    start_radius,
+                    end_radius,
+                    material,
+                ))
+            }
+            ShapeDescription::Capsule {
+                start,
+                end,
+                radius,
+                material,
+            } => {
+                if radius <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("capsule radius must be positive, got {}", radius),
+                    ));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Capsule::new(vector(start), vector(end), radius, material))
+            }
+            ShapeDescription::Torus {
+                center,
+                axis,
+                major_radius,
+                minor_radius,
+                material,
+            } => {
+                if vector(axis).length() == 0.0 {
+                    return Err(self.error(span, "torus axis must be non-zero"));
+                }
+                if major_radius <= 0.0 || minor_radius <= 0.0 {
+                    return Err(self.error(span, "torus radii must be positive"));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Torus::new(
+                    vector(center),
+                    vector(axis),
+                    major_radius,
+                    minor_radius,
+                    material,
+                ))
+            }
+            ShapeDescription::Triangle {
+                vertices,
+                normals,
+                uvs,
+                material,
+            } => {
+                let material = self.lookup_material(span, &material)?;
+                let mut triangle = Triangle::new(
+                    [
+                        vector(vertices[0]),
+                        vector(vertices[1]),
+                        vector(vertices[2]),
+                    ],
+                    material,
+                );
+                if let Some(normals) = normals {
+                    triangle = triangle.with_normals([
+                        vector(normals[0]),
+                        vector(normals[1]),
+                        vector(normals[2]),
+                    ]);
+                }
+                if let Some(uvs) = uvs {
+                    triangle = triangle.with_uvs([uv(uvs[0]), uv(uvs[1]), uv(uvs[2])]);
+                }
+                Box::new(triangle)
+            }
+            ShapeDescription::Csg {
+                operation,
+                left,
+                right,
+            } => {
+                let left = self.single_shape(span.clone(), *left)?;
+                let right = self.single_shape(span, *right)?;
+                Box::new(Csg::new(operation.into(), left, right))
+            }
+            ShapeDescription::Medium {
+                boundary,
+                density,
+                material,
+            } => {
+                if density <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("medium density must be positive, got {}", density),
+                    ));
+                }
+                let boundary = self.single_shape(span.clone(), *boundary)?;
+                let material = self.lookup_material(span, &material)?;
+                Box::new(ConstantMedium::new(boundary, density, material))
+            }
+            ShapeDescription::GridMedium {
+                path,
+                min,
+                max,
+                density,
+                material,
+            } => {
+                if (0..3).any(|axis| min[axis] >= max[axis]) {
+                    return Err(
+                        self.error(span, "grid medium min must be below max along every axis")
+                    );
+                }
+                if density <= 0.0 {
+                    return Err(self.error(
+                        span,
+                        format!("grid medium density must be positive, got {}", density),
+                    ));
+                }
+                let grid = DensityGrid::load(self.directory.join(&path))
+                    .map_err(|error| self.error(span.clone(), error.to_string()))?;
+                let material = self.lookup_material(span, &material)?;
+                Box::new(
+                    GridMedium::new(grid, vector(min), vector(max), material).with_density(density),
+                )
+            }
+            ShapeDescription::Obj { path, material } => {
+                // Each group is kept as its own object so emissive ones become lights.
+                return Ok(self
+                    .load_model(span, &path, material)?
+                    .into_iter()
+                    .map(|mesh| Box::new(mesh) as Box<dyn Collidable>)
+                    .collect());
+            }
+            ShapeDescription::Instance {
+                model,
+                translate,
+                rotate,
+                scale,
+                motion,
+            } => {
+                let meshes = self.models.get(&model).ok_or_else(|| {
+                    self.error(span.clone(), format!("unknown model `{}`", model))
+                })?;
+                if scale.contains(&0.0) {
+                    return Err(self.error(span, "instance scale must not be zero"));
+                }
+                let start = Placement {
+                    translate: vector(translate),
+                    rotate: vector(rotate),
+                    scale: vector(scale),
+                };
+                let motion = match motion {
+                    Some(motion) => {
+                        if motion.scale.is_some_and(|scale| scale.contains(&0.0)) {
+                            return Err(self.error(span, "instance scale must not be zero"));
+                        }
+                        if motion.end_time < motion.start_time {
+                            return Err(self.error(
+                                span,
+                                "instance motion end_time must not be before start_time",
+                            ));
+                        }
+                        let end = Placement {
+                            translate: motion.translate.map_or(start.translate, vector),
+                            rotate: motion.rotate.map_or(start.rotate, vector),
+                            scale: motion.scale.map_or(start.scale, vector),
+                        };
+                        // Blending between scales of opposite sign would pass through zero.
+                        if start.scale.x * end.scale.x < 0.0
+                            || start.scale.y * end.scale.y < 0.0
+                            || start.scale.z * end.scale.z < 0.0
+                        {
+                            return Err(self.error(
+                                span,
+                                "instance scale must not change sign during its motion",
+                            ));
+                        }
+                        Some((end, motion.start_time, motion.end_time))
+                    }
+                    None => None,
+                };
+                return Ok(meshes
+                    .iter()
+                    .map(|mesh| {
+                        let mesh = Arc::clone(mesh);
+                        match motion {
+                            Some((end, start_time, end_time)) => Box::new(Transformed::animated(
+                                mesh, start, end, start_time, end_time,
+                            ))
+                                as Box<dyn Collidable>,
+                            None => Box::new(Transformed::new(mesh, start.transform())),
+                        }
+                    })
+                    .collect());
+            }
+            ShapeDescription::Mesh {
+                positions,
+                normals,
+                uvs,
+                faces,
+                material,
+            } => {
+                if !normals.is_empty() && normals.len() != positions.len() {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "mesh has {} normals but {} positions",
+                            normals.len(),
+                            positions.len()
+                        ),
+                    ));
+                }
+                if !uvs.is_empty() && uvs.len() != positions.len() {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "mesh has {} uvs but {} positions",
+                            uvs.len(),
+                            positions.len()
+                        ),
+                    ));
+                }
+                if let Some(index) = faces
+                    .iter()
+                    .flatten()
+                    .find(|&&index| index >= positions.len())
+                {
+                    return Err(self.error(
+                        span,
+                        format!(
+                            "mesh face references vertex {} but there are only {} positions",
+                            index,
+                            positions.len()
+                        ),
+                    ));
+                }
+                let material = self.lookup_material(span, &material)?;
+                Box::new(Mesh::new(
+                    positions.into_iter().map(vector).collect(),
+                    normals.into_iter().map(vector).collect(),
+                    uvs.into_iter().map(uv).collect(),
+                    faces,
+                    material,
+                ))
+            }
+        };
+        Ok(vec![shape])
+    }
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene, SceneError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        Scene::parse_in(&source, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    pub fn parse(source: &str) -> Result<Scene, SceneError> {
+        Scene::parse_in(source, Path::new(""))
+    }
+
+    // Parses a scene whose relative file references (such as OBJ models) resolve against
+    // `directory`.
+    pub fn parse_in(source: &str, directory: &Path) -> Result<Scene, SceneError> {
+        let mut parser = Parser {
+            source,
+            directory,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            models: HashMap::new(),
+        };
+
+        let description: SceneDescription = toml::from_str(source).map_err(|error| {
+            parser.error(error.span().unwrap_or(0..0), error.message().to_string())
+        })?;
+
+        let camera = parser.camera(description.camera)?;
+
+        for (name, texture) in description.textures {
+            let texture = parser.texture(&name, texture)?;
+            parser.textures.insert(name, texture);
+        }
+
+        for (name, material) in description.materials {
+            let material = parser.material(&name, material)?;
+            parser.materials.insert(name, material);
+        }
+
+        for (name, model) in description.models {
+            let model = parser.model(model)?;
+            parser.models.insert(name, model);
+        }
+
+        let objects = description
+            .shapes
+            .into_iter()
+            .map(|shape| parser.shape(shape))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect();
+
+        let (environment, sky_sun) = match description.environment {
+            Some(environment) => parser.environment(environment)?,
+            None => (Arc::new(Gradient::default()) as Arc<dyn Environment>, None),
+        };
+        let mut world = World::new(objects);
+        if let Some(sun) = description.sun {
+            world = world.with_directional_light(parser.sun(sun, sky_sun)?);
+        }
+        if let Some(fog) = description.fog {
+            world = world.with_fog(parser.fog(fog)?);
+        }
+
+        Ok(Scene {
+            camera,
+            environment,
+            world,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAMERA: &str = "[camera]
+origin = [0.0, 0.0, 1.0]
+target = [0.0, 0.0, 0.0]
+field_of_view = 40.0
+";
+
+    fn invalid(source: &str) -> (usize, usize, String) {
+        match Scene::parse(source) {
+            Err(SceneError::Invalid {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            Err(error) => panic!("expected an invalid scene, got {}", error),
+            Ok(_) => panic!("expected an invalid scene, but it parsed"),
+        }
+    }
+
+    #[test]
+    fn parses_a_minimal_scene() {
+        assert!(Scene::parse(CAMERA).is_ok());
+    }
+
+    #[test]
+    fn reports_syntax_errors_where_they_occur() {
+        let (line, column, _) = invalid(&format!("{}aperture = ]\n", CAMERA));
+        assert_eq!((line, column), (5, 12));
+    }
+
+    #[test]
+    fn reports_unknown_fields() {
+        let (line, _, message) = invalid(&format!("{}zoom = 2.0\n", CAMERA));
+        assert_eq!(line, 5);
+        assert!(message.contains("zoom"), "{}", message);
+    }
+
+    #[test]
+    fn reports_a_missing_camera() {
+        let (line, column, message) = invalid("");
+        assert_eq!((line, column), (1, 1));
+        assert!(message.contains("camera"), "{}", message);
+    }
+
+    #[test]
+    fn reports_invalid_camera_settings_at_the_camera() {
+        let source = format!("\n{}aspect_ratio = -1.0\n", CAMERA);
+        let (line, column, message) = invalid(&source);
+        assert_eq!((line, column), (2, 1));
+        assert!(message.contains("aspect_ratio"), "{}", message);
+    }
+
+    #[test]
+    fn reports_unknown_materials_at_the_shape() {
+        let source = format!(
+            "{}
+[[shapes]]
+type = \"sphere\"
+center = [0.0, 0.0, 0.0]
+radius = 1.0
+material = \"missing\"
+",
+            CAMERA
+        );
+        let (line, _, message) = invalid(&source);
+        assert_eq!(line, 6);
+        assert_eq!(message, "unknown material `missing`");
+    }
+
+    #[test]
+    fn reports_instance_scales_that_change_sign() {
+        let source = format!(
+            "{}
+[models.block]
+path = \"models/cube.obj\"
+
+[[shapes]]
+type = \"instance\"
+model = \"block\"
+scale = [1.0, 1.0, 1.0]
+motion = {{ scale = [1.0, -1.0, 1.0], end_time = 1.0 }}
+",
+            CAMERA
+        );
+        let scenes = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        match Scene::parse_in(&source, &scenes) {
+            Err(SceneError::Invalid { line, message, .. }) => {
+                assert_eq!(line, 9);
+                assert_eq!(
+                    message,
+                    "instance scale must not change sign during its motion"
+                );
+            }
+            Err(error) => panic!("expected an invalid scene, got {}", error),
+            Ok(_) => panic!("expected an invalid scene, but it parsed"),
+        }
+
+        let mirrored = source.replace("scale = [1.0, 1.0, 1.0]", "scale = [1.0, -2.0, 1.0]");
+        assert!(Scene::parse_in(&mirrored, &scenes).is_ok());
+    }
+
+    #[test]
+    fn error_positions_count_from_one() {
+        let source = "ab\ncd\n";
+        let parser = Parser {
+            source,
+            directory: Path::new(""),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            models: HashMap::new(),
+        };
+        match parser.error(4..5, "here") {
+            SceneError::Invalid { line, column, .. } => assert_eq!((line, column), (2, 2)),
+            error => panic!("unexpected error {}", error),
+        }
+    }
+}
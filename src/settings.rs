@@ -0,0 +1,32 @@
+use std::sync::Arc;
+
+use crate::environment::{Environment, Gradient};
+
+#[derive(Clone)]
+pub struct RenderSettings {
+    pub samples: usize,
+    pub max_depth: usize,
+    pub epsilon: f64,
+    pub environment: Arc<dyn Environment>,
+}
+
+impl RenderSettings {
+    pub fn preview() -> RenderSettings {
+        RenderSettings {
+            samples: 4,
+            max_depth: 8,
+            ..RenderSettings::default()
+        }
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> RenderSettings {
+        RenderSettings {
+            samples: 100,
+            max_depth: 50,
+            epsilon: 0.001,
+            environment: Arc::new(Gradient::default()),
+        }
+    }
+}
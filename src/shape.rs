@@ -1,4 +1,11 @@
-use crate::collision::{Collidable, Collision};
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::f64;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::camera::random_unit_vector;
+use crate::collision::{area_to_solid_angle, Collidable, Collision, SurfaceSample};
 use crate::material::Material;
 use crate::ray::Ray;
 use crate::vector::Vector;
@@ -6,42 +13,973 @@ use crate::vector::Vector;
 pub struct Sphere {
     center: Vector,
     radius: f64,
-    material: Box<dyn Material>,
+    material: Arc<dyn Material>,
 }
 
 impl Sphere {
-    pub fn new(center: Vector, radius: f64, material: Box<dyn Material>) -> Sphere {
+    pub fn new(center: Vector, radius: f64, material: Arc<dyn Material>) -> Sphere {
         Sphere {
             center,
             radius,
             material,
         }
     }
+
+    // One minus the cosine of the half-angle of the cone the sphere subtends from `origin`,
+    // or `None` when `origin` is inside the sphere.
+    fn cone(&self, origin: Vector) -> Option<f64> {
+        let distance_squared = (self.center - origin).squared_length();
+        let radius_squared = self.radius.powi(2);
+        if distance_squared <= radius_squared {
+            return None;
+        }
+        let sin_squared = radius_squared / distance_squared;
+        Some(sin_squared / (1.0 + (1.0 - sin_squared).sqrt()))
+    }
+
+    fn area_pdf(&self) -> f64 {
+        1.0 / (4.0 * f64::consts::PI * self.radius.powi(2))
+    }
+}
+
+// Longitude and latitude of a point on the unit sphere, both mapped to [0, 1]: u runs around
+// the y axis starting from -x, and v from the bottom pole to the top.
+fn sphere_uv(normal: Vector) -> (f64, f64) {
+    let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+    let phi = (-normal.z).atan2(normal.x) + f64::consts::PI;
+    (phi / (2.0 * f64::consts::PI), theta / f64::consts::PI)
+}
+
+// The nearer intersection of `ray` with a sphere between `tmin` and `tmax`.
+fn intersect_sphere<'a>(
+    center: Vector,
+    radius: f64,
+    material: &'a dyn Material,
+    ray: Ray,
+    tmin: f64,
+    tmax: f64,
+) -> Option<Collision<'a>> {
+    let offset = ray.origin - center;
+
+    let a = ray.direction.dot(ray.direction);
+    let b = offset.dot(ray.direction);
+    let c = offset.dot(offset) - radius.powi(2);
+
+    let discriminant = b * b - a * c;
+    if discriminant > 0.0 {
+        let time = (-b - discriminant.sqrt()) / a;
+        if tmin < time && time < tmax {
+            let point = ray.point_at_parameter(time);
+            let normal = (point - center) / radius;
+            let (u, v) = sphere_uv(normal);
+            return Some(Collision::new(time, point, normal, material).with_uv(u, v));
+        }
+        let time = (-b + discriminant.sqrt()) / a;
+        if tmin < time && time < tmax {
+            let point = ray.point_at_parameter(time);
+            let normal = (point - center) / radius;
+            let (u, v) = sphere_uv(normal);
+            return Some(Collision::new(time, point, normal, material).with_uv(u, v));
+        }
+    }
+    None
 }
 
 impl Collidable for Sphere {
-    fn check_collision(self: &Self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision> {
-        let offset = ray.origin - self.center;
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        intersect_sphere(self.center, self.radius, &*self.material, ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vector::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.center - radius, self.center + radius)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
 
-        let a = ray.direction.dot(ray.direction);
-        let b = offset.dot(ray.direction);
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        let one_minus_cos_max = match self.cone(origin) {
+            Some(one_minus_cos_max) => one_minus_cos_max,
+            None => {
+                let normal = random_unit_vector(rng);
+                let point = self.center + normal * self.radius;
+                return Some(SurfaceSample {
+                    point,
+                    normal,
+                    pdf: area_to_solid_angle(self.area_pdf(), origin, point, normal),
+                });
+            }
+        };
+
+        // Sample the cone of directions the sphere subtends uniformly, then find where the
+        // chosen direction meets the sphere.
+        let axis = (self.center - origin).to_unit();
+        let (tangent, bitangent) = axis.basis();
+        let cosine = 1.0 - rng.gen::<f64>() * one_minus_cos_max;
+        let sine = (1.0 - cosine * cosine).max(0.0).sqrt();
+        let phi = 2.0 * f64::consts::PI * rng.gen::<f64>();
+        let direction =
+            tangent * (sine * phi.cos()) + bitangent * (sine * phi.sin()) + axis * cosine;
+
+        let offset = origin - self.center;
+        let b = offset.dot(direction);
         let c = offset.dot(offset) - self.radius.powi(2);
+        let time = -b - (b * b - c).max(0.0).sqrt();
+        let point = origin + direction * time;
+        Some(SurfaceSample {
+            point,
+            normal: (point - self.center) / self.radius,
+            pdf: 1.0 / (2.0 * f64::consts::PI * one_minus_cos_max),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        match self.cone(origin) {
+            Some(one_minus_cos_max) => 1.0 / (2.0 * f64::consts::PI * one_minus_cos_max),
+            None => area_to_solid_angle(self.area_pdf(), origin, collision.point, collision.normal),
+        }
+    }
+}
+
+// A sphere moving in a straight line from `start_center` at `start_time` to `end_center` at
+// `end_time`, holding still before and after. Only scattered rays can find it when it is
+// emissive, since light samples are not drawn at any particular time.
+pub struct MovingSphere {
+    start_center: Vector,
+    end_center: Vector,
+    start_time: f64,
+    end_time: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        start_center: Vector,
+        end_center: Vector,
+        start_time: f64,
+        end_time: f64,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> MovingSphere {
+        MovingSphere {
+            start_center,
+            end_center,
+            start_time,
+            end_time,
+            radius,
+            material,
+        }
+    }
+
+    fn center(&self, time: f64) -> Vector {
+        if self.end_time <= self.start_time {
+            return self.start_center;
+        }
+        let t = ((time - self.start_time) / (self.end_time - self.start_time)).clamp(0.0, 1.0);
+        self.start_center + (self.end_center - self.start_center) * t
+    }
+}
+
+impl Collidable for MovingSphere {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        intersect_sphere(
+            self.center(ray.time),
+            self.radius,
+            &*self.material,
+            ray,
+            tmin,
+            tmax,
+        )
+    }
+
+    // Covers the whole path, so the BVH finds the sphere whenever it is hit.
+    fn bounding_box(&self) -> Aabb {
+        let radius = Vector::new(self.radius, self.radius, self.radius);
+        Aabb::new(self.start_center - radius, self.start_center + radius).union(Aabb::new(
+            self.end_center - radius,
+            self.end_center + radius,
+        ))
+    }
+}
+
+pub struct Triangle {
+    vertices: [Vector; 3],
+    normals: Option<[Vector; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Arc<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Vector; 3], material: Arc<dyn Material>) -> Triangle {
+        Triangle {
+            vertices,
+            normals: None,
+            uvs: None,
+            material,
+        }
+    }
+
+    pub fn with_normals(self, normals: [Vector; 3]) -> Triangle {
+        Triangle {
+            normals: Some(normals),
+            ..self
+        }
+    }
+
+    pub fn with_uvs(self, uvs: [(f64, f64); 3]) -> Triangle {
+        Triangle {
+            uvs: Some(uvs),
+            ..self
+        }
+    }
+}
+
+impl Collidable for Triangle {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let (time, weights) = intersect_triangle(ray, &self.vertices, tmin, tmax)?;
+        Some(triangle_collision(
+            ray,
+            time,
+            weights,
+            &self.vertices,
+            self.normals.as_ref(),
+            self.uvs.as_ref(),
+            &*self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&self.vertices)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        let area = triangle_area(&self.vertices);
+        if area == 0.0 {
+            return None;
+        }
+        let (point, normal) = sample_triangle(&self.vertices, rng);
+        Some(SurfaceSample {
+            point,
+            normal,
+            pdf: area_to_solid_angle(1.0 / area, origin, point, normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        let area = triangle_area(&self.vertices);
+        if area == 0.0 {
+            return 0.0;
+        }
+        let normal = triangle_normal(&self.vertices);
+        area_to_solid_angle(1.0 / area, origin, collision.point, normal)
+    }
+}
+
+// Where `ray` crosses the plane through `point` with unit `normal`, if it does within
+// (tmin, tmax).
+fn intersect_plane(ray: Ray, point: Vector, normal: Vector, tmin: f64, tmax: f64) -> Option<f64> {
+    let denominator = normal.dot(ray.direction);
+    if denominator == 0.0 {
+        return None;
+    }
+    let time = (point - ray.origin).dot(normal) / denominator;
+    if time <= tmin || time >= tmax {
+        return None;
+    }
+    Some(time)
+}
+
+// An infinite plane. Its uvs are distances along two axes lying in the plane, so textures
+// repeat every unit.
+pub struct Plane {
+    point: Vector,
+    normal: Vector,
+    material: Arc<dyn Material>,
+}
+
+impl Plane {
+    pub fn new(point: Vector, normal: Vector, material: Arc<dyn Material>) -> Plane {
+        Plane {
+            point,
+            normal: normal.to_unit(),
+            material,
+        }
+    }
+}
+
+impl Collidable for Plane {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let time = intersect_plane(ray, self.point, self.normal, tmin, tmax)?;
+        let point = ray.point_at_parameter(time);
+        let (tangent, bitangent) = self.normal.basis();
+        let offset = point - self.point;
+        Some(
+            Collision::new(time, point, self.normal, &*self.material)
+                .with_uv(offset.dot(tangent), offset.dot(bitangent)),
+        )
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::infinite()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+}
+
+// A parallelogram with one corner at `corner` and sides along `edge_u` and `edge_v`, which
+// also run along the u and v texture axes. Its normal is `edge_u` cross `edge_v`.
+pub struct Quad {
+    corner: Vector,
+    edge_u: Vector,
+    edge_v: Vector,
+    normal: Vector,
+    // Scaled normal that turns a point in the plane into its coordinates along the edges.
+    projection: Vector,
+    material: Arc<dyn Material>,
+}
+
+impl Quad {
+    pub fn new(
+        corner: Vector,
+        edge_u: Vector,
+        edge_v: Vector,
+        material: Arc<dyn Material>,
+    ) -> Quad {
+        let cross = edge_u.cross(edge_v);
+        Quad {
+            corner,
+            edge_u,
+            edge_v,
+            normal: cross.to_unit(),
+            projection: cross / cross.dot(cross),
+            material,
+        }
+    }
+
+    fn area(&self) -> f64 {
+        self.edge_u.cross(self.edge_v).length()
+    }
+}
+
+impl Collidable for Quad {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let time = intersect_plane(ray, self.corner, self.normal, tmin, tmax)?;
+        let point = ray.point_at_parameter(time);
+        let offset = point - self.corner;
+        let u = self.projection.dot(offset.cross(self.edge_v));
+        let v = self.projection.dot(self.edge_u.cross(offset));
+        if !(0.0..=1.0).contains(&u) || !(0.0..=1.0).contains(&v) {
+            return None;
+        }
+        Some(Collision::new(time, point, self.normal, &*self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::from_points(&[
+            self.corner,
+            self.corner + self.edge_u,
+            self.corner + self.edge_v,
+            self.corner + self.edge_u + self.edge_v,
+        ])
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        let area = self.area();
+        if area == 0.0 {
+            return None;
+        }
+        let point = self.corner + self.edge_u * rng.gen::<f64>() + self.edge_v * rng.gen::<f64>();
+        Some(SurfaceSample {
+            point,
+            normal: self.normal,
+            pdf: area_to_solid_angle(1.0 / area, origin, point, self.normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        let area = self.area();
+        if area == 0.0 {
+            return 0.0;
+        }
+        area_to_solid_angle(1.0 / area, origin, collision.point, self.normal)
+    }
+}
+
+// A flat, round disk. u runs around it counterclockwise and v out from the center to the rim.
+pub struct Disk {
+    center: Vector,
+    normal: Vector,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vector, normal: Vector, radius: f64, material: Arc<dyn Material>) -> Disk {
+        Disk {
+            center,
+            normal: normal.to_unit(),
+            radius,
+            material,
+        }
+    }
+
+    fn area(&self) -> f64 {
+        f64::consts::PI * self.radius * self.radius
+    }
+}
+
+impl Collidable for Disk {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        let time = intersect_plane(ray, self.center, self.normal, tmin, tmax)?;
+        let point = ray.point_at_parameter(time);
+        let offset = point - self.center;
+        let distance_squared = offset.squared_length();
+        if distance_squared > self.radius * self.radius {
+            return None;
+        }
+        let (tangent, bitangent) = self.normal.basis();
+        let angle = offset.dot(bitangent).atan2(offset.dot(tangent));
+        let u = angle.rem_euclid(2.0 * f64::consts::PI) / (2.0 * f64::consts::PI);
+        let v = distance_squared.sqrt() / self.radius;
+        Some(Collision::new(time, point, self.normal, &*self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        // Each axis reaches as far as the rim can tilt along it.
+        let n = self.normal;
+        let reach = Vector::new(
+            (1.0 - n.x * n.x).max(0.0).sqrt(),
+            (1.0 - n.y * n.y).max(0.0).sqrt(),
+            (1.0 - n.z * n.z).max(0.0).sqrt(),
+        ) * self.radius;
+        Aabb::new(self.center - reach, self.center + reach)
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        let (tangent, bitangent) = self.normal.basis();
+        let distance = self.radius * rng.gen::<f64>().sqrt();
+        let angle = 2.0 * f64::consts::PI * rng.gen::<f64>();
+        let point =
+            self.center + tangent * (distance * angle.cos()) + bitangent * (distance * angle.sin());
+        Some(SurfaceSample {
+            point,
+            normal: self.normal,
+            pdf: area_to_solid_angle(1.0 / self.area(), origin, point, self.normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        area_to_solid_angle(1.0 / self.area(), origin, collision.point, self.normal)
+    }
+}
+
+// A box with faces perpendicular to the axes. Each face's uvs run from 0 to 1 along the two
+// axes it spans.
+pub struct AxisAlignedBox {
+    bounds: Aabb,
+    material: Arc<dyn Material>,
+}
+
+impl AxisAlignedBox {
+    pub fn new(min: Vector, max: Vector, material: Arc<dyn Material>) -> AxisAlignedBox {
+        AxisAlignedBox {
+            bounds: Aabb::new(min.min(max), min.max(max)),
+            material,
+        }
+    }
+
+    // Areas of one face perpendicular to each axis.
+    fn face_areas(&self) -> [f64; 3] {
+        let extent = self.bounds.extent();
+        [
+            extent.y * extent.z,
+            extent.z * extent.x,
+            extent.x * extent.y,
+        ]
+    }
 
-        let discriminant = b * b - a * c;
-        if discriminant > 0.0 {
-            let time = (-b - discriminant.sqrt()) / a;
-            if tmin < time && time < tmax {
-                let point = ray.point_at_parameter(time);
-                let normal = (point - self.center) / self.radius;
-                return Some(Collision::new(time, point, normal, &*self.material));
+    fn area(&self) -> f64 {
+        2.0 * self.face_areas().iter().sum::<f64>()
+    }
+
+    fn face_uv(&self, point: Vector, axis: usize) -> (f64, f64) {
+        let extent = self.bounds.extent();
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        (
+            (point[a] - self.bounds.min[a]) / extent[a],
+            (point[b] - self.bounds.min[b]) / extent[b],
+        )
+    }
+}
+
+fn axis_vector(axis: usize, length: f64) -> Vector {
+    match axis {
+        0 => Vector::new(length, 0.0, 0.0),
+        1 => Vector::new(0.0, length, 0.0),
+        _ => Vector::new(0.0, 0.0, length),
+    }
+}
+
+impl Collidable for AxisAlignedBox {
+    fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        // Slab test that remembers which axis the ray enters and leaves the box through.
+        let (mut near, mut far) = (f64::NEG_INFINITY, f64::INFINITY);
+        let (mut near_axis, mut far_axis) = (0, 0);
+        for axis in 0..3 {
+            let (min, max) = (self.bounds.min[axis], self.bounds.max[axis]);
+            if ray.direction[axis] == 0.0 {
+                if ray.origin[axis] < min || ray.origin[axis] > max {
+                    return None;
+                }
+                continue;
             }
-            let time = (-b + discriminant.sqrt()) / a;
-            if tmin < time && time < tmax {
-                let point = ray.point_at_parameter(time);
-                let normal = (point - self.center) / self.radius;
-                return Some(Collision::new(time, point, normal, &*self.material));
+            let t0 = (min - ray.origin[axis]) / ray.direction[axis];
+            let t1 = (max - ray.origin[axis]) / ray.direction[axis];
+            let (entry, exit) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+            if entry > near {
+                near = entry;
+                near_axis = axis;
             }
+            if exit < far {
+                far = exit;
+                far_axis = axis;
+            }
+        }
+        if near > far {
+            return None;
         }
-        None
+
+        // Normals point out of the box: against the ray where it enters, along it where it
+        // leaves.
+        let (time, axis, sign) = if tmin < near && near < tmax {
+            (near, near_axis, -ray.direction[near_axis].signum())
+        } else if tmin < far && far < tmax {
+            (far, far_axis, ray.direction[far_axis].signum())
+        } else {
+            return None;
+        };
+        let point = ray.point_at_parameter(time);
+        let (u, v) = self.face_uv(point, axis);
+        Some(Collision::new(time, point, axis_vector(axis, sign), &*self.material).with_uv(u, v))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn sample_surface(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        let area = self.area();
+        if area == 0.0 {
+            return None;
+        }
+        // Pick one of the six faces in proportion to its area, then a point on it.
+        let areas = self.face_areas();
+        let target = rng.gen::<f64>() * area * 0.5;
+        let axis = if target < areas[0] {
+            0
+        } else if target < areas[0] + areas[1] {
+            1
+        } else {
+            2
+        };
+        let high = rng.gen::<bool>();
+
+        let extent = self.bounds.extent();
+        let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+        let mut point = self.bounds.min
+            + axis_vector(a, extent[a] * rng.gen::<f64>())
+            + axis_vector(b, extent[b] * rng.gen::<f64>());
+        if high {
+            point = point + axis_vector(axis, extent[axis]);
+        }
+        let normal = axis_vector(axis, if high { 1.0 } else { -1.0 });
+        Some(SurfaceSample {
+            point,
+            normal,
+            pdf: area_to_solid_angle(1.0 / area, origin, point, normal),
+        })
+    }
+
+    fn surface_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        let area = self.area();
+        if area == 0.0 {
+            return 0.0;
+        }
+        area_to_solid_angle(1.0 / area, origin, collision.point, collision.normal)
+    }
+}
+
+// Watertight ray/triangle intersection (Woop, Benthin and Wald, 2013). Rays that pass exactly
+// through a shared edge or vertex hit at least one of the adjacent triangles, so meshes have no
+// cracks. Returns the hit time and the barycentric weights of each vertex.
+pub(crate) fn intersect_triangle(
+    ray: Ray,
+    vertices: &[Vector; 3],
+    tmin: f64,
+    tmax: f64,
+) -> Option<(f64, [f64; 3])> {
+    let direction = ray.direction;
+    let abs_direction = Vector::new(direction.x.abs(), direction.y.abs(), direction.z.abs());
+    let kz = if abs_direction.x > abs_direction.y && abs_direction.x > abs_direction.z {
+        0
+    } else if abs_direction.y > abs_direction.z {
+        1
+    } else {
+        2
+    };
+    let (kx, ky) = if direction[kz] < 0.0 {
+        ((kz + 2) % 3, (kz + 1) % 3)
+    } else {
+        ((kz + 1) % 3, (kz + 2) % 3)
+    };
+
+    let shear_x = direction[kx] / direction[kz];
+    let shear_y = direction[ky] / direction[kz];
+    let shear_z = 1.0 / direction[kz];
+
+    let a = vertices[0] - ray.origin;
+    let b = vertices[1] - ray.origin;
+    let c = vertices[2] - ray.origin;
+
+    let ax = a[kx] - shear_x * a[kz];
+    let ay = a[ky] - shear_y * a[kz];
+    let bx = b[kx] - shear_x * b[kz];
+    let by = b[ky] - shear_y * b[kz];
+    let cx = c[kx] - shear_x * c[kz];
+    let cy = c[ky] - shear_y * c[kz];
+
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return None;
+    }
+
+    let determinant = u + v + w;
+    if determinant == 0.0 {
+        return None;
+    }
+
+    let scaled_time = u * shear_z * a[kz] + v * shear_z * b[kz] + w * shear_z * c[kz];
+    let time = scaled_time / determinant;
+    if time <= tmin || time >= tmax {
+        return None;
+    }
+
+    Some((time, [u / determinant, v / determinant, w / determinant]))
+}
+
+pub(crate) fn triangle_collision<'a>(
+    ray: Ray,
+    time: f64,
+    weights: [f64; 3],
+    vertices: &[Vector; 3],
+    normals: Option<&[Vector; 3]>,
+    uvs: Option<&[(f64, f64); 3]>,
+    material: &'a dyn Material,
+) -> Collision<'a> {
+    let point = ray.point_at_parameter(time);
+    let normal = match normals {
+        Some(normals) => {
+            (normals[0] * weights[0] + normals[1] * weights[1] + normals[2] * weights[2]).to_unit()
+        }
+        None => triangle_normal(vertices),
+    };
+    let (u, v) = match uvs {
+        Some(uvs) => (
+            uvs[0].0 * weights[0] + uvs[1].0 * weights[1] + uvs[2].0 * weights[2],
+            uvs[0].1 * weights[0] + uvs[1].1 * weights[1] + uvs[2].1 * weights[2],
+        ),
+        None => (weights[1], weights[2]),
+    };
+    Collision::new(time, point, normal, material).with_uv(u, v)
+}
+
+pub(crate) fn triangle_normal(vertices: &[Vector; 3]) -> Vector {
+    (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .to_unit()
+}
+
+pub(crate) fn triangle_area(vertices: &[Vector; 3]) -> f64 {
+    (vertices[1] - vertices[0])
+        .cross(vertices[2] - vertices[0])
+        .length()
+        * 0.5
+}
+
+// Picks a point uniformly by area, returning it with the geometric normal.
+pub(crate) fn sample_triangle(vertices: &[Vector; 3], rng: &mut SmallRng) -> (Vector, Vector) {
+    let root = rng.gen::<f64>().sqrt();
+    let b = rng.gen::<f64>() * root;
+    let a = 1.0 - root;
+    let point = vertices[0] * a + vertices[1] * b + vertices[2] * (1.0 - a - b);
+    (point, triangle_normal(vertices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{DiffuseLight, Lambertian};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn close_vectors(a: Vector, b: Vector) -> bool {
+        (a - b).length() < 1e-9
+    }
+
+    fn light() -> Arc<dyn Material> {
+        Arc::new(DiffuseLight::new(Color::new(1.0, 1.0, 1.0)))
+    }
+
+    fn down(x: f64, y: f64) -> Ray {
+        Ray::new(Vector::new(x, y, 5.0), Vector::new(0.0, 0.0, -1.0), 0.0)
+    }
+
+    // Light samples seen from `origin` must hit the shape where they were drawn, with the pdf
+    // `surface_pdf` gives there. Samples hidden behind the shape's own front are skipped.
+    fn assert_samples_match_pdf(shape: &dyn Collidable, origin: Vector) {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let mut visible = 0;
+        for _ in 0..1000 {
+            let sample = shape.sample_surface(origin, &mut rng).unwrap();
+            let ray = Ray::new(origin, sample.point - origin, 0.0);
+            let collision = shape.check_collision(ray, 0.001, f64::MAX).unwrap();
+            if (collision.point - sample.point).length() > 1e-6 {
+                continue;
+            }
+            visible += 1;
+            assert!(close_vectors(collision.normal, sample.normal));
+            let pdf = shape.surface_pdf(origin, &collision);
+            assert!(
+                (pdf - sample.pdf).abs() < 1e-6 * sample.pdf,
+                "{} vs {}",
+                pdf,
+                sample.pdf
+            );
+        }
+        assert!(visible > 300, "only {} visible samples", visible);
+    }
+
+    // For a flat shape every sample is visible, so the mean of 1 / pdf is the solid angle it
+    // covers, which is also the fraction of random directions that hit it.
+    fn assert_pdf_covers_solid_angle(shape: &dyn Collidable, origin: Vector) {
+        let mut rng = SmallRng::seed_from_u64(5);
+        let count = 100_000;
+        let inverse_pdfs: f64 = (0..count)
+            .map(|_| 1.0 / shape.sample_surface(origin, &mut rng).unwrap().pdf)
+            .sum();
+        let hits = (0..count)
+            .filter(|_| {
+                let ray = Ray::new(origin, random_unit_vector(&mut rng), 0.0);
+                shape.check_collision(ray, 0.001, f64::MAX).is_some()
+            })
+            .count();
+        let sampled = inverse_pdfs / count as f64;
+        let expected = 4.0 * f64::consts::PI * hits as f64 / count as f64;
+        assert!(
+            (sampled - expected).abs() < 0.03 * expected,
+            "{} vs {}",
+            sampled,
+            expected
+        );
+    }
+
+    #[test]
+    fn plane_hits_from_either_side() {
+        let plane = Plane::new(
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.0, 0.0, 2.0),
+            Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+        );
+        let above = plane
+            .check_collision(down(0.5, 0.25), 0.001, f64::MAX)
+            .unwrap();
+        assert!(close(above.time, 4.0));
+        assert!(close_vectors(above.point, Vector::new(0.5, 0.25, 1.0)));
+        assert!(close_vectors(above.normal, Vector::new(0.0, 0.0, 1.0)));
+
+        let up = Ray::new(Vector::new(0.0, 0.0, -1.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let below = plane.check_collision(up, 0.001, f64::MAX).unwrap();
+        assert!(close(below.time, 2.0));
+        assert!(close_vectors(below.normal, Vector::new(0.0, 0.0, 1.0)));
+
+        assert!(plane.check_collision(down(0.0, 0.0), 0.001, 3.0).is_none());
+        let along = Ray::new(Vector::new(0.0, 0.0, 2.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+        assert!(plane.check_collision(along, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn plane_uvs_are_distances_in_the_plane() {
+        let plane = Plane::new(
+            Vector::new(0.0, 0.0, 1.0),
+            Vector::new(0.0, 0.0, 1.0),
+            Arc::new(Lambertian::new(Vector::new(0.5, 0.5, 0.5))),
+        );
+        let uv = |x: f64, y: f64| {
+            let collision = plane.check_collision(down(x, y), 0.001, f64::MAX).unwrap();
+            (collision.u, collision.v)
+        };
+        assert_eq!(uv(0.0, 0.0), (0.0, 0.0));
+        let (u, v) = uv(3.0, -4.0);
+        assert!(close(u.hypot(v), 5.0));
+        let ((u0, v0), (u1, v1)) = (uv(1.0, 2.0), uv(1.0, 3.0));
+        assert!(close((u1 - u0).hypot(v1 - v0), 1.0));
+    }
+
+    #[test]
+    fn quad_hits_inside_its_edges() {
+        // A parallelogram leaning to the right.
+        let quad = Quad::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(1.0, 3.0, 0.0),
+            light(),
+        );
+        let collision = quad
+            .check_collision(down(1.5, 1.5), 0.001, f64::MAX)
+            .unwrap();
+        assert!(close(collision.time, 5.0));
+        assert!(close_vectors(collision.normal, Vector::new(0.0, 0.0, 1.0)));
+        assert!(close(collision.u, 0.5) && close(collision.v, 0.5));
+
+        let corner = quad
+            .check_collision(down(2.9, 2.7), 0.001, f64::MAX)
+            .unwrap();
+        assert!(close(corner.u, 1.0) && close(corner.v, 0.9));
+
+        // Inside the bounding box but outside the slanted edges.
+        assert!(quad
+            .check_collision(down(0.2, 2.0), 0.001, f64::MAX)
+            .is_none());
+        assert!(quad
+            .check_collision(down(2.5, 0.5), 0.001, f64::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn disk_uvs_run_around_and_out() {
+        let disk = Disk::new(
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+            2.0,
+            light(),
+        );
+        let at = |x: f64, y: f64| disk.check_collision(down(x, y), 0.001, f64::MAX);
+
+        let collision = at(2.0, 1.0).unwrap();
+        assert!(close(collision.time, 5.0));
+        assert!(close_vectors(collision.normal, Vector::new(0.0, 0.0, 1.0)));
+        assert!(close(collision.v, 0.5));
+        assert!(close(at(1.0, 2.5).unwrap().v, 0.75));
+        assert!(at(2.5, 2.5).is_none());
+
+        // A small step counterclockwise about the normal moves u forward by the same fraction
+        // of a turn.
+        let step: f64 = 0.1;
+        let start = at(2.0, 1.0).unwrap().u;
+        let end = at(1.0 + step.cos(), 1.0 + step.sin()).unwrap().u;
+        assert!(close(
+            (end - start).rem_euclid(1.0),
+            step / (2.0 * f64::consts::PI)
+        ));
+    }
+
+    #[test]
+    fn box_normals_point_out_of_the_face_hit() {
+        let cube = AxisAlignedBox::new(
+            Vector::new(1.0, 2.0, 3.0),
+            Vector::new(0.0, 0.0, 0.0),
+            light(),
+        );
+
+        let outside = cube
+            .check_collision(down(0.5, 1.0), 0.001, f64::MAX)
+            .unwrap();
+        assert!(close(outside.time, 2.0));
+        assert!(close_vectors(outside.normal, Vector::new(0.0, 0.0, 1.0)));
+        assert!(close(outside.u, 0.5) && close(outside.v, 0.5));
+
+        // From inside, the ray leaves through the far face.
+        let inside = Ray::new(Vector::new(0.5, 1.0, 1.5), Vector::new(1.0, 0.0, 0.0), 0.0);
+        let collision = cube.check_collision(inside, 0.001, f64::MAX).unwrap();
+        assert!(close(collision.time, 0.5));
+        assert!(close_vectors(collision.normal, Vector::new(1.0, 0.0, 0.0)));
+        assert!(close(collision.u, 0.5) && close(collision.v, 0.5));
+
+        let inside = Ray::new(
+            Vector::new(0.5, 1.5, 0.75),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let collision = cube.check_collision(inside, 0.001, f64::MAX).unwrap();
+        assert!(close(collision.time, 1.5));
+        assert!(close_vectors(collision.normal, Vector::new(0.0, -1.0, 0.0)));
+        assert!(close(collision.u, 0.25) && close(collision.v, 0.5));
+
+        assert!(cube
+            .check_collision(down(1.5, 1.0), 0.001, f64::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn quad_samples_match_pdf() {
+        let quad = Quad::new(
+            Vector::new(-1.0, 0.0, -1.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.5, 0.5, 2.0),
+            light(),
+        );
+        let origin = Vector::new(0.3, 2.0, 0.1);
+        assert_samples_match_pdf(&quad, origin);
+        assert_pdf_covers_solid_angle(&quad, origin);
+    }
+
+    #[test]
+    fn disk_samples_match_pdf() {
+        let disk = Disk::new(
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(1.0, -1.0, 0.5),
+            1.5,
+            light(),
+        );
+        let origin = Vector::new(2.0, -0.5, 0.5);
+        assert_samples_match_pdf(&disk, origin);
+        assert_pdf_covers_solid_angle(&disk, origin);
+    }
+
+    #[test]
+    fn box_samples_match_pdf() {
+        let cube = AxisAlignedBox::new(
+            Vector::new(-1.0, 0.0, -2.0),
+            Vector::new(1.0, 0.5, 1.0),
+            light(),
+        );
+        assert_samples_match_pdf(&cube, Vector::new(3.0, 2.0, 4.0));
     }
 }
@@ -0,0 +1,191 @@
+use std::f64;
+
+use crate::color::Color;
+use crate::environment::Environment;
+use crate::vector::Vector;
+
+// The five Perez coefficients describing how one sky quantity varies over the dome.
+#[derive(Clone, Copy, Debug)]
+struct Perez {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+}
+
+impl Perez {
+    // `cos_theta` is the cosine of the view direction's angle from the zenith and `gamma` its
+    // angle from the sun.
+    fn distribution(&self, cos_theta: f64, gamma: f64) -> f64 {
+        let cos_gamma = gamma.cos();
+        (1.0 + self.a * (self.b / cos_theta.max(0.001)).exp())
+            * (1.0 + self.c * (self.d * gamma).exp() + self.e * cos_gamma * cos_gamma)
+    }
+}
+
+// One channel of the Yxy sky: its value at the zenith, and the distribution at the zenith
+// that every other direction is measured relative to.
+#[derive(Clone, Copy, Debug)]
+struct SkyChannel {
+    perez: Perez,
+    zenith: f64,
+    normalization: f64,
+}
+
+impl SkyChannel {
+    fn new(perez: Perez, zenith: f64, sun_theta: f64) -> SkyChannel {
+        SkyChannel {
+            perez,
+            zenith,
+            normalization: perez.distribution(1.0, sun_theta),
+        }
+    }
+
+    fn value(&self, cos_theta: f64, gamma: f64) -> f64 {
+        self.zenith * self.perez.distribution(cos_theta, gamma) / self.normalization
+    }
+}
+
+fn polynomial(coefficients: [f64; 4], theta: f64) -> f64 {
+    ((coefficients[0] * theta + coefficients[1]) * theta + coefficients[2]) * theta
+        + coefficients[3]
+}
+
+// Sky luminance comes out of the model in kcd/m², around 1 to 30 for a clear day. It is
+// scaled down so that a typical zenith renders near 0.3 before exposure.
+const LUMINANCE_SCALE: f64 = 0.03;
+
+// Preetham, Shirley and Smits' analytic daylight model ("A Practical Analytic Model for
+// Daylight", 1999). Only the sky is modelled: the sun itself is a separate directional light.
+// Below the horizon the sky is replaced by a diffuse ground lit by it.
+pub struct Sky {
+    sun_direction: Vector,
+    luminance: SkyChannel,
+    x: SkyChannel,
+    y: SkyChannel,
+    ground: Color,
+    intensity: f64,
+}
+
+impl Sky {
+    // `sun_direction` points from the scene towards the sun; it is clamped to the horizon.
+    // `turbidity` runs from about 2 for a very clear sky to 10 for a hazy one.
+    pub fn new(sun_direction: Vector, turbidity: f64, ground_albedo: Color) -> Sky {
+        let sun_direction =
+            Vector::new(sun_direction.x, sun_direction.y.max(0.0), sun_direction.z).to_unit();
+        let sun_theta = sun_direction.y.clamp(0.0, 1.0).acos();
+        let t = turbidity;
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (f64::consts::PI - 2.0 * sun_theta);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |t2: [f64; 4], t1: [f64; 4], t0: [f64; 4]| {
+            t * t * polynomial(t2, sun_theta)
+                + t * polynomial(t1, sun_theta)
+                + polynomial(t0, sun_theta)
+        };
+        let zenith_x = chromaticity(
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        );
+        let zenith_y = chromaticity(
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        );
+
+        let luminance = Perez {
+            a: 0.1787 * t - 1.4630,
+            b: -0.3554 * t + 0.4275,
+            c: -0.0227 * t + 5.3251,
+            d: 0.1206 * t - 2.5771,
+            e: -0.0670 * t + 0.3703,
+        };
+        let x = Perez {
+            a: -0.0193 * t - 0.2592,
+            b: -0.0665 * t + 0.0008,
+            c: -0.0004 * t + 0.2125,
+            d: -0.0641 * t - 0.8989,
+            e: -0.0033 * t + 0.0452,
+        };
+        let y = Perez {
+            a: -0.0167 * t - 0.2608,
+            b: -0.0950 * t + 0.0092,
+            c: -0.0079 * t + 0.2102,
+            d: -0.0441 * t - 1.6537,
+            e: -0.0109 * t + 0.0529,
+        };
+
+        let mut sky = Sky {
+            sun_direction,
+            luminance: SkyChannel::new(luminance, zenith_luminance.max(0.0), sun_theta),
+            x: SkyChannel::new(x, zenith_x, sun_theta),
+            y: SkyChannel::new(y, zenith_y, sun_theta),
+            ground: Color::default(),
+            intensity: 1.0,
+        };
+        sky.ground = ground_albedo * sky.irradiance() * (1.0 / f64::consts::PI);
+        sky
+    }
+
+    pub fn with_intensity(self, intensity: f64) -> Sky {
+        Sky { intensity, ..self }
+    }
+
+    pub fn sun_direction(&self) -> Vector {
+        self.sun_direction
+    }
+
+    fn sky_radiance(&self, direction: Vector) -> Color {
+        let cos_theta = direction.y.max(0.0);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let luminance = self.luminance.value(cos_theta, gamma) * LUMINANCE_SCALE;
+        let x = self.x.value(cos_theta, gamma);
+        let y = self.y.value(cos_theta, gamma);
+        if y <= 0.0 || luminance <= 0.0 {
+            return Color::default();
+        }
+
+        // CIE xyY to XYZ, then to linear sRGB primaries.
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+        Color::new(
+            (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+            (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+            (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+        )
+    }
+
+    // Irradiance the sky delivers to a horizontal surface, integrated numerically over the
+    // upper hemisphere.
+    fn irradiance(&self) -> Color {
+        let (rings, segments) = (32, 64);
+        let d_theta = f64::consts::FRAC_PI_2 / rings as f64;
+        let d_phi = 2.0 * f64::consts::PI / segments as f64;
+        let mut total = Color::default();
+        for ring in 0..rings {
+            let theta = (ring as f64 + 0.5) * d_theta;
+            let (sin_theta, cos_theta) = theta.sin_cos();
+            for segment in 0..segments {
+                let phi = (segment as f64 + 0.5) * d_phi;
+                let direction =
+                    Vector::new(sin_theta * phi.cos(), cos_theta, sin_theta * phi.sin());
+                total = total
+                    + self.sky_radiance(direction) * (cos_theta * sin_theta * d_theta * d_phi);
+            }
+        }
+        total
+    }
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: Vector) -> Color {
+        let radiance = if direction.y < 0.0 {
+            self.ground
+        } else {
+            self.sky_radiance(direction)
+        };
+        radiance * self.intensity
+    }
+}
@@ -0,0 +1,78 @@
+// How the two eye views share one frame. The left eye takes the left or top half, which is
+// what most headsets and players expect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoLayout {
+    SideBySide,
+    TopBottom,
+}
+
+impl StereoLayout {
+    // The size of a frame holding two views of `width` by `height`.
+    pub fn frame_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            StereoLayout::SideBySide => (width * 2, height),
+            StereoLayout::TopBottom => (width, height * 2),
+        }
+    }
+
+    // The size of each view in a frame of `width` by `height`.
+    pub fn view_size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            StereoLayout::SideBySide => (width / 2, height),
+            StereoLayout::TopBottom => (width, height / 2),
+        }
+    }
+
+    // Where the second view starts in a frame of `width` by `height`.
+    pub fn right_offset(self, width: usize, height: usize) -> (usize, usize) {
+        let (view_width, view_height) = self.view_size(width, height);
+        match self {
+            StereoLayout::SideBySide => (view_width, 0),
+            StereoLayout::TopBottom => (0, view_height),
+        }
+    }
+}
+
+// A pair of eyes `interocular` apart, turned in so that their views agree on whatever is
+// `convergence` away. An infinite convergence keeps them parallel.
+#[derive(Clone, Copy, Debug)]
+pub struct Stereo {
+    pub interocular: f64,
+    pub convergence: f64,
+    pub layout: StereoLayout,
+}
+
+impl Stereo {
+    pub fn new(interocular: f64, convergence: f64) -> Stereo {
+        Stereo {
+            interocular,
+            convergence,
+            layout: StereoLayout::SideBySide,
+        }
+    }
+
+    pub fn with_layout(self, layout: StereoLayout) -> Stereo {
+        Stereo { layout, ..self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn side_by_side_puts_the_right_eye_on_the_right() {
+        let layout = StereoLayout::SideBySide;
+        assert_eq!(layout.frame_size(64, 48), (128, 48));
+        assert_eq!(layout.view_size(128, 48), (64, 48));
+        assert_eq!(layout.right_offset(128, 48), (64, 0));
+    }
+
+    #[test]
+    fn top_bottom_puts_the_right_eye_below() {
+        let layout = StereoLayout::TopBottom;
+        assert_eq!(layout.frame_size(64, 48), (64, 96));
+        assert_eq!(layout.view_size(64, 96), (64, 48));
+        assert_eq!(layout.right_offset(64, 96), (0, 48));
+    }
+}
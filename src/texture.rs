@@ -0,0 +1,181 @@
+use itertools::iproduct;
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::camera::random_unit_vector;
+use crate::color::Color;
+use crate::vector::Vector;
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: Vector) -> Color;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SolidColor {
+    color: Color,
+}
+
+impl SolidColor {
+    pub fn new(color: Color) -> SolidColor {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: Vector) -> Color {
+        self.color
+    }
+}
+
+// A 3D checkerboard of cubes `scale` units wide, so it wraps any shape without seams.
+pub struct Checker {
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+    scale: f64,
+}
+
+impl Checker {
+    pub fn new(even: Arc<dyn Texture>, odd: Arc<dyn Texture>, scale: f64) -> Checker {
+        Checker { even, odd, scale }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, u: f64, v: f64, point: Vector) -> Color {
+        let cell = (point.x / self.scale).floor()
+            + (point.y / self.scale).floor()
+            + (point.z / self.scale).floor();
+        if cell as i64 % 2 == 0 {
+            self.even.value(u, v, point)
+        } else {
+            self.odd.value(u, v, point)
+        }
+    }
+}
+
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl ImageTexture {
+    pub fn load<P: AsRef<Path>>(path: P) -> image::ImageResult<ImageTexture> {
+        let image = image::open(path)?.to_rgb();
+        let (width, height) = image.dimensions();
+        let pixels = image
+            .pixels()
+            .map(|pixel| Color::from_srgb(pixel.data[0], pixel.data[1], pixel.data[2]))
+            .collect();
+        Ok(ImageTexture {
+            width: width as usize,
+            height: height as usize,
+            pixels,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: Vector) -> Color {
+        if self.pixels.is_empty() {
+            return Color::default();
+        }
+        // Wrap so tiled uvs repeat the image; v runs bottom to top.
+        let u = u - u.floor();
+        let v = 1.0 - (v - v.floor());
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+const PERLIN_POINTS: usize = 256;
+
+// Ken Perlin's gradient noise, with gradients and permutations drawn from a seeded generator
+// so every render of a scene sees the same pattern.
+pub struct Perlin {
+    gradients: Vec<Vector>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let gradients = (0..PERLIN_POINTS)
+            .map(|_| random_unit_vector(&mut rng))
+            .collect();
+        let mut permutation = || {
+            let mut points: Vec<usize> = (0..PERLIN_POINTS).collect();
+            points.shuffle(&mut rng);
+            points
+        };
+        let permutations = [permutation(), permutation(), permutation()];
+        Perlin {
+            gradients,
+            permutations,
+        }
+    }
+
+    pub fn noise(&self, point: Vector) -> f64 {
+        let floor = Vector::new(point.x.floor(), point.y.floor(), point.z.floor());
+        let fraction = point - floor;
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let (u, v, w) = (smooth(fraction.x), smooth(fraction.y), smooth(fraction.z));
+
+        let mut total = 0.0;
+        for (i, e, k) in iproduct!(0..2, 0..2, 0..2) {
+            let index = self.permutations[0][(floor.x as i64 + i as i64) as usize & 255]
+                ^ self.permutations[1][(floor.y as i64 + e as i64) as usize & 255]
+                ^ self.permutations[2][(floor.z as i64 + k as i64) as usize & 255];
+            let weight = Vector::new(
+                fraction.x - i as f64,
+                fraction.y - e as f64,
+                fraction.z - k as f64,
+            );
+            let (i, e, k) = (i as f64, e as f64, k as f64);
+            total += (i * u + (1.0 - i) * (1.0 - u))
+                * (e * v + (1.0 - e) * (1.0 - v))
+                * (k * w + (1.0 - k) * (1.0 - w))
+                * self.gradients[index].dot(weight);
+        }
+        total
+    }
+
+    pub fn turbulence(&self, point: Vector, depth: usize) -> f64 {
+        let mut total = 0.0;
+        let mut point = point;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            total += weight * self.noise(point);
+            weight *= 0.5;
+            point = point * 2.0;
+        }
+        total.abs()
+    }
+}
+
+// Marble-like veins of `color` built from turbulent Perlin noise.
+pub struct Noise {
+    perlin: Perlin,
+    color: Color,
+    scale: f64,
+}
+
+impl Noise {
+    pub fn new(color: Color, scale: f64, seed: u64) -> Noise {
+        Noise {
+            perlin: Perlin::new(seed),
+            color,
+            scale,
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _u: f64, _v: f64, point: Vector) -> Color {
+        let phase = self.scale * point.z + 10.0 * self.perlin.turbulence(point, 7);
+        self.color * (0.5 * (1.0 + phase.sin()))
+    }
+}
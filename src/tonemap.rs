@@ -0,0 +1,170 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::color::Color;
+
+// How scene radiance above 1.0 is squeezed into the displayable range.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ToneMap {
+    #[default]
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl ToneMap {
+    pub fn apply(self, color: Color) -> Color {
+        let map = |channel: f64| {
+            let channel = channel.max(0.0);
+            match self {
+                ToneMap::Clamp => channel.min(1.0),
+                ToneMap::Reinhard => channel / (1.0 + channel),
+                // Krzysztof Narkowicz's fit of the ACES filmic reference curve.
+                ToneMap::Aces => {
+                    let mapped = (channel * (2.51 * channel + 0.03))
+                        / (channel * (2.43 * channel + 0.59) + 0.14);
+                    mapped.clamp(0.0, 1.0)
+                }
+            }
+        };
+        Color::new(map(color.red), map(color.green), map(color.blue))
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<ToneMap, String> {
+        match name {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "aces" => Ok(ToneMap::Aces),
+            _ => Err(format!(
+                "unknown tone map `{}`, expected clamp, reinhard or aces",
+                name
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ToneMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ToneMap::Clamp => "clamp",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Aces => "aces",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// Turns the linear radiance a camera renders into display values: scale by the exposure,
+// tone map, then encode with the sRGB transfer curve.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PostProcess {
+    // In stops, so each +1 doubles the brightness.
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+}
+
+impl PostProcess {
+    pub fn expose(&self, color: Color) -> Color {
+        color * self.exposure.exp2()
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        self.tone_map.apply(self.expose(color)).to_srgb()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn gray(value: f64) -> Color {
+        Color::new(value, value, value)
+    }
+
+    #[test]
+    fn clamp_cuts_off_outside_the_display_range() {
+        let mapped = ToneMap::Clamp.apply(Color::new(-0.5, 0.25, 3.0));
+        assert_eq!((mapped.red, mapped.green, mapped.blue), (0.0, 0.25, 1.0));
+    }
+
+    #[test]
+    fn reinhard_known_values() {
+        for &(input, output) in &[(0.0, 0.0), (1.0, 0.5), (3.0, 0.75), (-1.0, 0.0)] {
+            assert!(close(ToneMap::Reinhard.apply(gray(input)).red, output));
+        }
+    }
+
+    #[test]
+    fn aces_known_values() {
+        assert!(close(ToneMap::Aces.apply(gray(0.0)).red, 0.0));
+        assert!(close(ToneMap::Aces.apply(gray(1.0)).red, 2.54 / 3.16));
+        // The fit overshoots 1 for bright input, which is clamped.
+        assert_eq!(ToneMap::Aces.apply(gray(1000.0)).red, 1.0);
+        let mut previous = 0.0;
+        for step in 1..100 {
+            let mapped = ToneMap::Aces.apply(gray(step as f64 * 0.1)).red;
+            assert!(mapped >= previous);
+            previous = mapped;
+        }
+    }
+
+    #[test]
+    fn parses_its_own_names() {
+        for &tone_map in &[ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces] {
+            assert_eq!(tone_map.to_string().parse(), Ok(tone_map));
+        }
+        assert!("filmic".parse::<ToneMap>().is_err());
+    }
+
+    #[test]
+    fn exposure_is_in_stops() {
+        let post = PostProcess {
+            exposure: 1.0,
+            tone_map: ToneMap::Clamp,
+        };
+        assert!(close(post.expose(gray(0.25)).red, 0.5));
+        let darker = PostProcess {
+            exposure: -2.0,
+            ..post
+        };
+        assert!(close(darker.expose(gray(1.0)).red, 0.25));
+        // Exposure applies before the tone map.
+        assert!(close(post.apply(gray(0.75)).red, 1.0));
+    }
+
+    #[test]
+    fn srgb_encoding_known_values() {
+        let encode = |value: f64| gray(value).to_srgb().red;
+        assert_eq!(encode(0.0), 0.0);
+        assert!(close(encode(1.0), 1.0));
+        // Linear below the threshold, a power curve above it.
+        assert!(close(encode(0.002), 0.002 * 12.92));
+        assert!(close(encode(0.003_130_8), 0.003_130_8 * 12.92));
+        assert!(close(encode(0.5), 1.055 * 0.5f64.powf(1.0 / 2.4) - 0.055));
+        assert_eq!(encode(-1.0), 0.0);
+        assert!(close(encode(2.0), 1.0));
+
+        assert_eq!(Color::from_srgb(0, 0, 0).red, 0.0);
+        assert!(close(Color::from_srgb(255, 255, 255).red, 1.0));
+        assert!(close(
+            Color::from_srgb(10, 10, 10).red,
+            10.0 / 255.0 / 12.92
+        ));
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for value in 0..=255u8 {
+            let encoded = Color::from_srgb(value, value, value).to_srgb().red;
+            assert!(close(encoded * 255.0, f64::from(value)), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use std::ops::Mul;
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut product = [[0.0; 4]; 4];
+    for (row, product_row) in product.iter_mut().enumerate() {
+        for (column, value) in product_row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    product
+}
+
+fn transpose(matrix: &Matrix) -> Matrix {
+    let mut transposed = [[0.0; 4]; 4];
+    for (row, values) in matrix.iter().enumerate() {
+        for (column, value) in values.iter().enumerate() {
+            transposed[column][row] = *value;
+        }
+    }
+    transposed
+}
+
+// An affine transform as a 4x4 matrix, kept together with its inverse so neither direction
+// ever needs a general matrix inversion. Composing with `*` applies the right hand side first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    pub fn translate(offset: Vector) -> Transform {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    pub fn scale(factors: Vector) -> Transform {
+        assert!(
+            factors.x != 0.0 && factors.y != 0.0 && factors.z != 0.0,
+            "cannot scale by zero: {:?}",
+            factors
+        );
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][axis] = factors[axis];
+            inverse[axis][axis] = 1.0 / factors[axis];
+        }
+        Transform { matrix, inverse }
+    }
+
+    // A counterclockwise rotation by `degrees` around `axis`, looking down the axis towards
+    // the origin.
+    pub fn rotate(axis: Vector, degrees: f64) -> Transform {
+        let axis = axis.to_unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let a = [axis.x, axis.y, axis.z];
+        let cross = [
+            [0.0, -axis.z, axis.y],
+            [axis.z, 0.0, -axis.x],
+            [-axis.y, axis.x, 0.0],
+        ];
+
+        let mut matrix = IDENTITY;
+        for row in 0..3 {
+            for column in 0..3 {
+                let diagonal = if row == column { cos } else { 0.0 };
+                matrix[row][column] =
+                    diagonal + sin * cross[row][column] + (1.0 - cos) * a[row] * a[column];
+            }
+        }
+        Transform {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, point: Vector) -> Vector {
+        let m = &self.matrix;
+        Vector::new(
+            m[0][0] * point.x + m[0][1] * point.y + m[0][2] * point.z + m[0][3],
+            m[1][0] * point.x + m[1][1] * point.y + m[1][2] * point.z + m[1][3],
+            m[2][0] * point.x + m[2][1] * point.y + m[2][2] * point.z + m[2][3],
+        )
+    }
+
+    pub fn vector(&self, vector: Vector) -> Vector {
+        let m = &self.matrix;
+        Vector::new(
+            m[0][0] * vector.x + m[0][1] * vector.y + m[0][2] * vector.z,
+            m[1][0] * vector.x + m[1][1] * vector.y + m[1][2] * vector.z,
+            m[2][0] * vector.x + m[2][1] * vector.y + m[2][2] * vector.z,
+        )
+    }
+
+    // Normals transform by the inverse transpose to stay perpendicular to the surface. The
+    // result is not normalized.
+    pub fn normal(&self, normal: Vector) -> Vector {
+        let m = &self.inverse;
+        Vector::new(
+            m[0][0] * normal.x + m[1][0] * normal.y + m[2][0] * normal.z,
+            m[0][1] * normal.x + m[1][1] * normal.y + m[2][1] * normal.z,
+            m[0][2] * normal.x + m[1][2] * normal.y + m[2][2] * normal.z,
+        )
+    }
+
+    // The direction is left unnormalized so ray parameters match on both sides.
+    pub fn ray(&self, ray: Ray) -> Ray {
+        Ray::new(self.point(ray.origin), self.vector(ray.direction), ray.time)
+    }
+
+    pub fn bounding_box(&self, aabb: Aabb) -> Aabb {
+        if !aabb.is_finite() {
+            return Aabb::infinite();
+        }
+        let corners: Vec<Vector> = (0..8)
+            .map(|corner| {
+                self.point(Vector::new(
+                    if corner & 1 == 0 {
+                        aabb.min.x
+                    } else {
+                        aabb.max.x
+                    },
+                    if corner & 2 == 0 {
+                        aabb.min.y
+                    } else {
+                        aabb.max.y
+                    },
+                    if corner & 4 == 0 {
+                        aabb.min.z
+                    } else {
+                        aabb.max.z
+                    },
+                ))
+            })
+            .collect();
+        Aabb::from_points(&corners)
+    }
+
+    // How much a small patch of surface with unit `normal` grows in area when transformed.
+    pub fn area_scale(&self, normal: Vector) -> f64 {
+        let m = &self.matrix;
+        let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        determinant.abs() * self.normal(normal).length()
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Transform {
+            matrix: multiply(&self.matrix, &rhs.matrix),
+            inverse: multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+// A transform described by its parts: scaled first, then rotated around the x, y and z axes in
+// turn (in degrees), then translated. Unlike a matrix, these can be blended between two poses.
+#[derive(Clone, Copy, Debug)]
+pub struct Placement {
+    pub translate: Vector,
+    pub rotate: Vector,
+    pub scale: Vector,
+}
+
+impl Placement {
+    pub fn transform(&self) -> Transform {
+        Transform::translate(self.translate)
+            * Transform::rotate(Vector::new(0.0, 0.0, 1.0), self.rotate.z)
+            * Transform::rotate(Vector::new(0.0, 1.0, 0.0), self.rotate.y)
+            * Transform::rotate(Vector::new(1.0, 0.0, 0.0), self.rotate.x)
+            * Transform::scale(self.scale)
+    }
+
+    // Blends each part separately, going from `self` at `t` = 0 to `other` at `t` = 1.
+    pub fn lerp(&self, other: &Placement, t: f64) -> Placement {
+        let mix = |a: Vector, b: Vector| a + (b - a) * t;
+        Placement {
+            translate: mix(self.translate, other.translate),
+            rotate: mix(self.rotate, other.rotate),
+            scale: mix(self.scale, other.scale),
+        }
+    }
+}
+
+impl Default for Placement {
+    fn default() -> Placement {
+        Placement {
+            translate: Vector::default(),
+            rotate: Vector::default(),
+            scale: Vector::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(found: Vector, expected: Vector) {
+        assert!(
+            (found - expected).length() < 1e-9,
+            "{:?} vs {:?}",
+            found,
+            expected
+        );
+    }
+
+    // A rotation, a non-uniform scale with a mirrored axis and a translation, so no part of
+    // the matrix is trivial.
+    fn skewed() -> Transform {
+        Transform::translate(Vector::new(1.0, -2.0, 3.0))
+            * Transform::rotate(Vector::new(1.0, 2.0, -0.5), 37.0)
+            * Transform::scale(Vector::new(2.0, -0.5, 3.0))
+    }
+
+    #[test]
+    fn composes_right_to_left() {
+        let transform = Transform::translate(Vector::new(1.0, 0.0, 0.0))
+            * Transform::scale(Vector::unit() * 2.0);
+        assert_close(
+            transform.point(Vector::new(1.0, 1.0, 1.0)),
+            Vector::new(3.0, 2.0, 2.0),
+        );
+        // Vectors ignore translation.
+        assert_close(
+            transform.vector(Vector::new(1.0, 1.0, 1.0)),
+            Vector::new(2.0, 2.0, 2.0),
+        );
+
+        let quarter = Transform::rotate(Vector::new(0.0, 0.0, 1.0), 90.0);
+        assert_close(
+            quarter.point(Vector::new(1.0, 0.0, 0.0)),
+            Vector::new(0.0, 1.0, 0.0),
+        );
+        assert_close(
+            (quarter * quarter).point(Vector::new(1.0, 0.0, 0.0)),
+            Vector::new(-1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let transform = skewed();
+        let point = Vector::new(0.3, -4.0, 2.5);
+        assert_close(transform.inverse().point(transform.point(point)), point);
+        assert_close(transform.point(transform.inverse().point(point)), point);
+        assert_close(transform.inverse().vector(transform.vector(point)), point);
+
+        let identity = transform * transform.inverse();
+        for axis in &[
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, 0.0),
+            Vector::new(0.0, 0.0, 1.0),
+        ] {
+            assert_close(identity.point(*axis), *axis);
+        }
+        assert_eq!(transform.inverse().inverse(), transform);
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let transform = skewed();
+        let normal = Vector::new(1.0, 1.0, 1.0).to_unit();
+        let (tangent, bitangent) = normal.basis();
+        let transformed = transform.normal(normal);
+        assert!(transformed.dot(transform.vector(tangent)).abs() < 1e-9);
+        assert!(transformed.dot(transform.vector(bitangent)).abs() < 1e-9);
+        // A plain vector would not be.
+        assert!(
+            transform
+                .vector(normal)
+                .dot(transform.vector(tangent))
+                .abs()
+                > 1e-3
+        );
+    }
+
+    #[test]
+    fn area_scale_matches_transformed_triangles() {
+        let transform = skewed();
+        let vertices = [
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.2, -0.3),
+            Vector::new(0.4, 1.0, 0.5),
+        ];
+        let area = |[a, b, c]: [Vector; 3]| (b - a).cross(c - a).length() / 2.0;
+        let normal = (vertices[1] - vertices[0])
+            .cross(vertices[2] - vertices[0])
+            .to_unit();
+        let moved = [
+            transform.point(vertices[0]),
+            transform.point(vertices[1]),
+            transform.point(vertices[2]),
+        ];
+        let ratio = area(moved) / area(vertices);
+        assert!(
+            (transform.area_scale(normal) - ratio).abs() < 1e-9,
+            "{} vs {}",
+            transform.area_scale(normal),
+            ratio
+        );
+    }
+
+    #[test]
+    fn bounding_box_holds_transformed_corners() {
+        let transform = skewed();
+        let bounds = Aabb::new(Vector::new(-1.0, 0.0, 2.0), Vector::new(1.0, 3.0, 4.0));
+        let moved = transform.bounding_box(bounds);
+        for corner in 0..8 {
+            let point = transform.point(Vector::new(
+                if corner & 1 == 0 { -1.0 } else { 1.0 },
+                if corner & 2 == 0 { 0.0 } else { 3.0 },
+                if corner & 4 == 0 { 2.0 } else { 4.0 },
+            ));
+            for axis in 0..3 {
+                assert!(moved.min[axis] <= point[axis] && point[axis] <= moved.max[axis]);
+            }
+        }
+        assert!(!transform.bounding_box(Aabb::infinite()).is_finite());
+    }
+
+    #[test]
+    fn placements_scale_then_rotate_then_translate() {
+        let placement = Placement {
+            translate: Vector::new(0.0, 0.0, 5.0),
+            rotate: Vector::new(0.0, 0.0, 90.0),
+            scale: Vector::new(2.0, 1.0, 1.0),
+        };
+        assert_close(
+            placement.transform().point(Vector::new(1.0, 0.0, 0.0)),
+            Vector::new(0.0, 2.0, 5.0),
+        );
+
+        let halfway = Placement::default().lerp(&placement, 0.5);
+        assert_close(halfway.translate, Vector::new(0.0, 0.0, 2.5));
+        assert_close(halfway.rotate, Vector::new(0.0, 0.0, 45.0));
+        assert_close(halfway.scale, Vector::new(1.5, 1.0, 1.0));
+    }
+}
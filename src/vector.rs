@@ -1,4 +1,4 @@
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Vector {
@@ -12,14 +12,6 @@ impl Vector {
         Vector { x, y, z }
     }
 
-    pub fn default() -> Vector {
-        Vector {
-            x: 0.0,
-            y: 0.0,
-            z: 0.0,
-        }
-    }
-
     pub fn unit() -> Vector {
         Vector {
             x: 1.0,
@@ -28,29 +20,92 @@ impl Vector {
         }
     }
 
-    pub fn squared_length(self: &Self) -> f64 {
+    pub fn squared_length(&self) -> f64 {
         self.x.powi(2) + self.y.powi(2) + self.z.powi(2)
     }
 
-    pub fn length(self: &Self) -> f64 {
+    pub fn length(&self) -> f64 {
         self.squared_length().sqrt()
     }
 
-    pub fn dot(self: &Self, other: Self) -> f64 {
+    pub fn dot(&self, other: Self) -> f64 {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
-    pub fn cross(self: &Self, other: Self) -> Vector {
+    pub fn cross(&self, other: Self) -> Vector {
         Vector {
             x: self.y * other.z - self.z * other.y,
-            y: -1.0 * (self.x * other.z - self.z * other.x),
+            y: -(self.x * other.z - self.z * other.x),
             z: self.x * other.y - self.y * other.x,
         }
     }
 
-    pub fn to_unit(self: &Self) -> Vector {
+    pub fn to_unit(&self) -> Vector {
         *self / self.length()
     }
+
+    // Two unit vectors that, together with this (unit) vector, form an orthonormal basis
+    // (Duff et al., "Building an Orthonormal Basis, Revisited").
+    pub fn basis(&self) -> (Vector, Vector) {
+        let sign = 1f64.copysign(self.z);
+        let a = -1.0 / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vector::new(1.0 + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vector::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+
+    pub fn min(&self, other: Self) -> Vector {
+        Vector {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+            z: self.z.min(other.z),
+        }
+    }
+
+    pub fn max(&self, other: Self) -> Vector {
+        Vector {
+            x: self.x.max(other.x),
+            y: self.y.max(other.y),
+            z: self.z.max(other.z),
+        }
+    }
+}
+
+impl Default for Vector {
+    fn default() -> Vector {
+        Vector {
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+}
+
+impl Index<usize> for Vector {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector axis out of range: {}", axis),
+        }
+    }
+}
+
+impl Neg for Vector {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Vector {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
 }
 
 impl Add for Vector {
@@ -0,0 +1,426 @@
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::f64;
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::collision::{Collidable, Collision};
+use crate::color::Color;
+use crate::grid::DensityGrid;
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::vector::Vector;
+
+// How much medium a ray gets through before scattering, in units of optical depth (density
+// times distance). Free paths are exponentially distributed, so the chance of getting through
+// a stretch of medium is its transmittance.
+fn sample_optical_depth(rng: &mut SmallRng) -> f64 {
+    -(1.0 - rng.gen::<f64>()).ln()
+}
+
+fn scattering<'a>(ray: Ray, time: f64, material: &'a dyn Material) -> Collision<'a> {
+    // Volumes have no surface; the normal only needs to be well defined.
+    Collision::new(
+        time,
+        ray.point_at_parameter(time),
+        -ray.direction.to_unit(),
+        material,
+    )
+}
+
+// Smoke, mist or any other uniform participating medium filling a closed `boundary`. Rays
+// passing through scatter somewhere inside with a probability that grows with `density`, and
+// `phase_function` (usually `Isotropic`) decides where they go from there.
+pub struct ConstantMedium {
+    boundary: Box<dyn Collidable>,
+    density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Box<dyn Collidable>,
+        density: f64,
+        phase_function: Arc<dyn Material>,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+impl Collidable for ConstantMedium {
+    // Media are only found by `sample_collision`, which has random numbers to pick where the
+    // ray scatters.
+    fn check_collision(&self, _ray: Ray, _tmin: f64, _tmax: f64) -> Option<Collision<'_>> {
+        None
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        // A single free path is measured across every span inside the boundary in turn.
+        let mut optical_depth = sample_optical_depth(rng);
+        let rate = self.density * ray.direction.length();
+        for interval in self.boundary.intervals(ray, tmin, tmax) {
+            let start = interval.entry.map_or(tmin, |collision| collision.time);
+            let end = interval.exit.map_or(tmax, |collision| collision.time);
+            let span = (end - start) * rate;
+            if optical_depth < span {
+                let time = start + optical_depth / rate;
+                return Some(scattering(ray, time, &*self.phase_function));
+            }
+            optical_depth -= span;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, _rng: &mut SmallRng) -> f64 {
+        let inside: f64 = self
+            .boundary
+            .intervals(ray, tmin, tmax)
+            .iter()
+            .map(|interval| {
+                let start = interval.entry.map_or(tmin, |collision| collision.time);
+                let end = interval.exit.map_or(tmax, |collision| collision.time);
+                end - start
+            })
+            .sum();
+        (-self.density * ray.direction.length() * inside).exp()
+    }
+}
+
+// Homogeneous fog filling the world up to `ceiling`, for atmospheric haze. Without a ceiling
+// it never ends, and hides anything far enough away, the environment included.
+pub struct Fog {
+    density: f64,
+    ceiling: f64,
+    phase_function: Isotropic,
+}
+
+impl Fog {
+    pub fn new(density: f64, albedo: Color) -> Fog {
+        Fog {
+            density,
+            ceiling: f64::INFINITY,
+            phase_function: Isotropic::new(albedo),
+        }
+    }
+
+    pub fn with_ceiling(self, ceiling: f64) -> Fog {
+        Fog { ceiling, ..self }
+    }
+
+    // The part of the ray between `tmin` and `tmax` below the ceiling.
+    fn span(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<(f64, f64)> {
+        if self.ceiling == f64::INFINITY {
+            return Some((tmin, tmax));
+        }
+        if ray.direction.y == 0.0 {
+            return if ray.origin.y < self.ceiling {
+                Some((tmin, tmax))
+            } else {
+                None
+            };
+        }
+        let crossing = (self.ceiling - ray.origin.y) / ray.direction.y;
+        let (start, end) = if ray.direction.y > 0.0 {
+            (tmin, tmax.min(crossing))
+        } else {
+            (tmin.max(crossing), tmax)
+        };
+        if start < end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+}
+
+impl Collidable for Fog {
+    fn check_collision(&self, _ray: Ray, _tmin: f64, _tmax: f64) -> Option<Collision<'_>> {
+        None
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        let (start, end) = self.span(ray, tmin, tmax)?;
+        let time = start + sample_optical_depth(rng) / (self.density * ray.direction.length());
+        if time < end {
+            Some(scattering(ray, time, &self.phase_function))
+        } else {
+            None
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        Aabb::infinite()
+    }
+
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, _rng: &mut SmallRng) -> f64 {
+        match self.span(ray, tmin, tmax) {
+            Some((start, end)) => (-self.density * ray.direction.length() * (end - start)).exp(),
+            None => 1.0,
+        }
+    }
+}
+
+// A heterogeneous medium such as simulated smoke, whose density comes from a voxel grid
+// stretched over the box from `min` to `max` and multiplied by `density`. Rays are tracked
+// through it against the grid's largest density: delta tracking picks where they scatter
+// and ratio tracking estimates transmittance for shadow rays, both without bias.
+pub struct GridMedium {
+    grid: DensityGrid,
+    bounds: Aabb,
+    density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: DensityGrid,
+        min: Vector,
+        max: Vector,
+        phase_function: Arc<dyn Material>,
+    ) -> GridMedium {
+        let bounds = Aabb::new(min, max);
+        GridMedium {
+            grid,
+            bounds,
+            density: 1.0,
+            phase_function,
+        }
+    }
+
+    pub fn with_density(self, density: f64) -> GridMedium {
+        GridMedium { density, ..self }
+    }
+
+    fn density_at(&self, point: Vector) -> f64 {
+        let offset = point - self.bounds.min;
+        let extent = self.bounds.extent();
+        let local = Vector::new(
+            offset.x / extent.x,
+            offset.y / extent.y,
+            offset.z / extent.z,
+        );
+        self.grid.density(local) * self.density
+    }
+
+    fn majorant(&self) -> f64 {
+        self.grid.max() * self.density
+    }
+}
+
+impl Collidable for GridMedium {
+    fn check_collision(&self, _ray: Ray, _tmin: f64, _tmax: f64) -> Option<Collision<'_>> {
+        None
+    }
+
+    fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        let majorant = self.majorant();
+        if majorant <= 0.0 {
+            return None;
+        }
+        let (start, end) = self.bounds.clip(ray, tmin, tmax)?;
+        let rate = majorant * ray.direction.length();
+
+        // Delta tracking: step by free paths through a medium as dense as the densest voxel,
+        // and treat each stop as a real collision with probability density / majorant.
+        let mut time = start;
+        loop {
+            time -= (1.0 - rng.gen::<f64>()).ln() / rate;
+            if time >= end {
+                return None;
+            }
+            let point = ray.point_at_parameter(time);
+            if rng.gen::<f64>() * majorant < self.density_at(point) {
+                return Some(scattering(ray, time, &*self.phase_function));
+            }
+        }
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, rng: &mut SmallRng) -> f64 {
+        let majorant = self.majorant();
+        let (start, end) = match self.bounds.clip(ray, tmin, tmax) {
+            Some(span) if majorant > 0.0 => span,
+            _ => return 1.0,
+        };
+        let rate = majorant * ray.direction.length();
+
+        // Ratio tracking: the same steps, but each one scales the transmittance by the chance
+        // it was not a real collision instead of deciding outright.
+        let mut transmittance = 1.0;
+        let mut time = start;
+        loop {
+            time -= (1.0 - rng.gen::<f64>()).ln() / rate;
+            if time >= end || transmittance == 0.0 {
+                return transmittance;
+            }
+            let point = ray.point_at_parameter(time);
+            transmittance *= 1.0 - self.density_at(point) / majorant;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shape::AxisAlignedBox;
+
+    const SAMPLES: usize = 20_000;
+
+    fn phase() -> Arc<dyn Material> {
+        Arc::new(Isotropic::new(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn unit_box_medium(density: f64) -> ConstantMedium {
+        let boundary = AxisAlignedBox::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            phase(),
+        );
+        ConstantMedium::new(Box::new(boundary), density, phase())
+    }
+
+    fn uniform_grid_medium(density: f64) -> GridMedium {
+        GridMedium::new(
+            DensityGrid::new(2, 2, 2, vec![1.0; 8]),
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            phase(),
+        )
+        .with_density(density)
+    }
+
+    // Crosses the unit box along x, one unit of medium in all.
+    fn crossing() -> Ray {
+        Ray::new(Vector::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0), 0.0)
+    }
+
+    fn passed_through<C: Collidable>(medium: &C, rng: &mut SmallRng) -> f64 {
+        let passed = (0..SAMPLES)
+            .filter(|_| {
+                medium
+                    .sample_collision(crossing(), 0.0, f64::MAX, rng)
+                    .is_none()
+            })
+            .count();
+        passed as f64 / SAMPLES as f64
+    }
+
+    #[test]
+    fn constant_medium_free_paths_match_beer_lambert() {
+        let mut rng = SmallRng::seed_from_u64(1);
+        let medium = unit_box_medium(1.5);
+        let expected = (-1.5f64).exp();
+        assert!((passed_through(&medium, &mut rng) - expected).abs() < 0.01);
+        assert!(
+            (medium.transmittance(crossing(), 0.0, f64::MAX, &mut rng) - expected).abs() < 1e-9
+        );
+    }
+
+    #[test]
+    fn constant_medium_scatters_inside_boundary() {
+        let mut rng = SmallRng::seed_from_u64(2);
+        let medium = unit_box_medium(3.0);
+        for _ in 0..1000 {
+            if let Some(collision) = medium.sample_collision(crossing(), 0.0, f64::MAX, &mut rng) {
+                assert!((0.0..=1.0).contains(&collision.point.x));
+            }
+        }
+    }
+
+    #[test]
+    fn identical_rays_scatter_at_different_points() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let medium = unit_box_medium(3.0);
+        let mut times: Vec<f64> = (0..100)
+            .filter_map(|_| medium.sample_collision(crossing(), 0.0, f64::MAX, &mut rng))
+            .map(|collision| collision.time)
+            .collect();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times.dedup();
+        assert!(times.len() > 50);
+    }
+
+    #[test]
+    fn media_depend_on_the_seed() {
+        let medium = unit_box_medium(1.0);
+        let sample = |seed| {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            (0..10)
+                .map(|_| {
+                    medium
+                        .sample_collision(crossing(), 0.0, f64::MAX, &mut rng)
+                        .map_or(f64::INFINITY, |collision| collision.time)
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_ne!(sample(1), sample(2));
+    }
+
+    #[test]
+    fn grid_medium_delta_tracking_matches_beer_lambert() {
+        let mut rng = SmallRng::seed_from_u64(4);
+        let medium = uniform_grid_medium(1.5);
+        let expected = (-1.5f64).exp();
+        assert!((passed_through(&medium, &mut rng) - expected).abs() < 0.01);
+    }
+
+    #[test]
+    fn grid_medium_ratio_tracking_mean_matches_beer_lambert() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        // The ray runs halfway between the two voxels, through a density of 2 against a
+        // majorant of 3, so ratio tracking has null collisions to weigh.
+        let grid = DensityGrid::new(1, 1, 2, vec![1.0, 3.0]);
+        let medium = GridMedium::new(
+            grid,
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            phase(),
+        )
+        .with_density(0.75);
+        let mean = (0..SAMPLES)
+            .map(|_| medium.transmittance(crossing(), 0.0, f64::MAX, &mut rng))
+            .sum::<f64>()
+            / SAMPLES as f64;
+        assert!((mean - (-1.5f64).exp()).abs() < 0.01);
+    }
+
+    #[test]
+    fn fog_free_paths_match_beer_lambert() {
+        let mut rng = SmallRng::seed_from_u64(6);
+        let fog = Fog::new(0.5, Color::new(1.0, 1.0, 1.0));
+        let ray = Ray::new(Vector::default(), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let passed = (0..SAMPLES)
+            .filter(|_| fog.sample_collision(ray, 0.0, 2.0, &mut rng).is_none())
+            .count();
+        assert!((passed as f64 / SAMPLES as f64 - (-1.0f64).exp()).abs() < 0.01);
+    }
+}
@@ -1,31 +1,150 @@
-use crate::collision::{Collidable, Collision};
+use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::sync::Arc;
+
+use crate::bvh::Bvh;
+use crate::collision::{Collidable, Collision, SurfaceSample};
+use crate::light::DirectionalLight;
 use crate::ray::Ray;
+use crate::vector::Vector;
+use crate::volume::Fog;
 
 pub struct World {
-    collidable_objects: Vec<Box<dyn Collidable>>,
+    collidable_objects: Bvh<Arc<dyn Collidable>>,
+    // Objects without finite bounds, such as planes, which a BVH cannot partition.
+    unbounded_objects: Vec<Arc<dyn Collidable>>,
+    lights: Vec<Arc<dyn Collidable>>,
+    directional_lights: Vec<DirectionalLight>,
+    fog: Option<Fog>,
 }
 
 impl World {
     pub fn new(collidable_objects: Vec<Box<dyn Collidable>>) -> World {
-        World { collidable_objects }
-    }
-
-    pub fn check_collision(self: &Self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision> {
-        let mut closest_collision: Option<Collision> = None;
-        for collidable in self.collidable_objects.iter() {
-            if let Some(collision) = collidable.check_collision(ray, tmin, tmax) {
-                closest_collision = match closest_collision {
-                    Some(closest_collision) => {
-                        if closest_collision.time < collision.time {
-                            Some(closest_collision)
-                        } else {
-                            Some(collision)
-                        }
-                    }
-                    None => Some(collision),
-                };
+        let collidable_objects: Vec<Arc<dyn Collidable>> =
+            collidable_objects.into_iter().map(Arc::from).collect();
+        let lights = collidable_objects
+            .iter()
+            .filter(|collidable| collidable.is_emissive())
+            .cloned()
+            .collect();
+        let (bounded, unbounded_objects) = collidable_objects
+            .into_iter()
+            .partition(|collidable| collidable.bounding_box().is_finite());
+        World {
+            collidable_objects: Bvh::new(bounded),
+            unbounded_objects,
+            lights,
+            directional_lights: Vec::new(),
+            fog: None,
+        }
+    }
+
+    pub fn with_directional_light(mut self, light: DirectionalLight) -> World {
+        self.directional_lights.push(light);
+        self
+    }
+
+    pub fn with_fog(self, fog: Fog) -> World {
+        World {
+            fog: Some(fog),
+            ..self
+        }
+    }
+
+    // The closest collision `hit` finds among everything in the world, given each object and
+    // the distance to beat. Fog goes last, so it only has to cover the gap left in front of
+    // whatever else was hit.
+    fn closest<'a, F>(&'a self, tmax: f64, mut hit: F) -> Option<Collision<'a>>
+    where
+        F: FnMut(&'a dyn Collidable, f64) -> Option<Collision<'a>>,
+    {
+        let mut closest = hit(&self.collidable_objects, tmax);
+        for object in &self.unbounded_objects {
+            let limit = closest.map_or(tmax, |collision| collision.time);
+            if let Some(collision) = hit(&**object, limit) {
+                closest = Some(collision);
+            }
+        }
+        if let Some(fog) = &self.fog {
+            let limit = closest.map_or(tmax, |collision| collision.time);
+            if let Some(collision) = hit(fog, limit) {
+                closest = Some(collision);
+            }
+        }
+        closest
+    }
+
+    // The closest surface along the ray. Participating media need random numbers to be
+    // found, so only `sample_collision` sees them.
+    pub fn check_collision(&self, ray: Ray, tmin: f64, tmax: f64) -> Option<Collision<'_>> {
+        self.closest(tmax, |object, limit| {
+            object.check_collision(ray, tmin, limit)
+        })
+    }
+
+    // Where the ray stops, whether on a surface or by scattering inside a medium.
+    pub fn sample_collision(
+        &self,
+        ray: Ray,
+        tmin: f64,
+        tmax: f64,
+        rng: &mut SmallRng,
+    ) -> Option<Collision<'_>> {
+        self.closest(tmax, |object, limit| {
+            object.sample_collision(ray, tmin, limit, rng)
+        })
+    }
+
+    // The fraction of light that gets along the ray from `tmin` to `tmax` through everything
+    // in the world, for shadow rays.
+    pub fn transmittance(&self, ray: Ray, tmin: f64, tmax: f64, rng: &mut SmallRng) -> f64 {
+        let mut transmittance = self.collidable_objects.transmittance(ray, tmin, tmax, rng);
+        for object in &self.unbounded_objects {
+            if transmittance == 0.0 {
+                return 0.0;
+            }
+            transmittance *= object.transmittance(ray, tmin, tmax, rng);
+        }
+        if let Some(fog) = &self.fog {
+            transmittance *= fog.transmittance(ray, tmin, tmax, rng);
+        }
+        transmittance
+    }
+
+    pub fn lights(&self) -> &[Arc<dyn Collidable>] {
+        &self.lights
+    }
+
+    pub fn directional_lights(&self) -> &[DirectionalLight] {
+        &self.directional_lights
+    }
+
+    // Picks one of the emissive objects uniformly and a point on it to sample direct
+    // lighting at `origin`. The sample's pdf accounts for the choice of light.
+    pub fn sample_light(&self, origin: Vector, rng: &mut SmallRng) -> Option<SurfaceSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let light = &self.lights[rng.gen_range(0, self.lights.len())];
+        let sample = light.sample_surface(origin, rng)?;
+        Some(SurfaceSample {
+            pdf: sample.pdf / self.lights.len() as f64,
+            ..sample
+        })
+    }
+
+    // The density with which `sample_light(origin)` picks `collision`, a point found by
+    // tracing a ray from `origin`; zero if it does not lie on any light.
+    pub fn light_pdf(&self, origin: Vector, collision: &Collision) -> f64 {
+        // Lights are never animated, so the time does not matter.
+        let ray = Ray::new(origin, collision.point - origin, 0.0);
+        for light in self.lights.iter() {
+            if let Some(hit) = light.check_collision(ray, 1e-6, 1.0 + 1e-6) {
+                if (hit.time - 1.0).abs() < 1e-6 {
+                    return light.surface_pdf(origin, &hit) / self.lights.len() as f64;
+                }
             }
         }
-        closest_collision
+        0.0
     }
 }